@@ -5,3 +5,139 @@ welcome = Welcome to COSMIC! ✨
 page-id = Page { $num }
 git-description = Git commit {$hash} on {$date}
 example-row = Example Row
+
+## Errors shown in the popup
+
+error-login = Couldn't log in to your Claude account.
+error-token-refresh = Your session expired and couldn't be renewed.
+error-usage = Couldn't load your usage from Claude.
+error-credentials = Your saved login couldn't be read.
+error-credentials-save = Your login couldn't be saved. Saving is retried in the background, but you will be logged out if the applet restarts first.
+error-credentials-move = Your login couldn't be moved to the chosen storage. It is still stored where it was.
+error-request-id = Request ID: { $id }
+error-copy-details = Copy details
+error-dismiss = Dismiss
+error-action-retry = Try again
+error-action-login = Log in again
+
+## Extra usage
+
+# Amount of extra usage credits in US dollars. $amount already has the separators of
+# the user's locale.
+credits-amount = ${ $amount }
+extra-usage = Extra usage
+extra-usage-spent = { $spent } spent this month
+extra-usage-spent-of-limit = { $spent } of { $limit } spent this month
+extra-usage-projected = Projected by the end of the month: { $projected }
+budget-none = None
+budget-within = Within your budget of { $budget }
+budget-projected-over = On pace to exceed your budget of { $budget }
+budget-over = Over your budget of { $budget }
+budget-alert-over = Extra usage over budget
+budget-alert-over-body = { $spent } spent this month, your budget is { $budget }.
+budget-alert-projected-over = Extra usage on pace to exceed budget
+budget-alert-projected-over-body = { $projected } projected by the end of the month, your budget is { $budget }.
+
+## Usage periods, named after the fields of the API response
+
+period-five-hour = 5-hour session
+period-seven-day = Weekly, all models
+period-seven-day-opus = Weekly, Opus
+period-seven-day-sonnet = Weekly, Sonnet
+period-seven-day-oauth-apps = Weekly, OAuth apps
+period-iguana-necktie = 5-hour, additional limit
+period-seven-day-iguana-necktie = Weekly, additional limit
+
+## Durations and times
+
+minutes = { $minutes } min
+seconds = { $seconds } s
+updated-just-now = Updated just now
+updated-minutes-ago = Updated { $minutes } min ago
+updated-hours-ago = Updated { $hours } h ago
+updated-days-ago = Updated { $days } d ago
+resetting-now = Resetting now
+# $time is the local weekday and time of the reset.
+resets-at = Resets { $time }
+resets-in-minutes = Resets in { $minutes } m
+resets-in-hours = Resets in { $hours } h
+resets-in-hours-minutes = Resets in { $hours } h { $minutes } m
+
+## Popup
+
+login = Login
+add-account = Add account
+default-account = Default account
+no-usage-data = No usage data yet
+open-details = Open details
+settings = Settings
+
+## Panel tooltip
+
+hover-not-logged-in = Claude usage: not logged in
+hover-no-usage-data = Claude usage: no data yet
+hover-period-usage = { $period }: { $utilization }%
+hover-next-reset = { $reset } ({ $period })
+
+## Settings
+
+settings-usage-periods = Usage periods
+settings-polling = Polling
+settings-poll-interval = Poll interval
+settings-panel = Panel
+settings-panel-content = Show
+settings-panel-period = Usage period
+settings-alerts = Alerts
+settings-warning-threshold = Warning from
+settings-critical-threshold = Critical from
+settings-extra-usage-budget = Extra usage budget
+settings-advanced = Advanced
+settings-credential-backend = Credential storage
+settings-log-level = Log level
+panel-content-icon = Icon
+panel-content-percentage = Percentage
+panel-content-icon-and-percentage = Icon and percentage
+panel-content-bar = Bar
+credential-backend-file = File
+credential-backend-secret-service = Secret Service
+log-level-error = Errors
+log-level-warn = Warnings
+log-level-info = Information
+log-level-debug = Debug
+log-level-trace = Trace
+
+## Details window
+
+details-title = Claude usage
+details-not-logged-in = Log in from the applet to see your usage
+details-account = Account
+details-profile = Profile
+details-token-expiry = Access token expires
+details-token-expiry-unknown = Unknown
+details-other-accounts = Other accounts
+details-usage = Usage
+details-history = Last 24 hours
+details-history-start = 24 h ago
+details-history-end = Now
+
+## Diagnostics
+
+diagnostics = Diagnostics
+diagnostics-credentials-not-saved = Credentials not saved: { $details }
+polling-by-other-instance = Another applet instance polls the usage, refreshing asks it to update
+polling-on-battery = on battery
+polling-on-ac-power = on AC power
+polling-metered-network = metered network
+polling-unmetered-network = unmetered network
+polling-every = Polling every { $every } ({ $power_source }, { $network })
+monitor-other-instance = Updated by another applet instance
+monitor-paused-suspended = Paused while the system is suspended
+monitor-updating = Updating…
+monitor-next-update = Next update at { $time }
+monitor-waiting = Waiting for the first update
+# $current is one of the other monitor states, $latency is in milliseconds.
+monitor-last-update = { $current }. Last successful update at { $time } ({ $latency } ms)
+stale-offline = Offline
+stale-update-failed = Couldn't update usage
+stale-showing-usage-from = { $reason }, showing usage from { $time }
+stale-no-usage-data = { $reason }, no usage data yet
@@ -1,11 +1,93 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use crate::budget::{self, BudgetStatus};
 use crate::claude;
-use crate::claude_monitor::claude_usage_monitoring;
-use cosmic::iced::{Length, Limits, Subscription, window::Id};
+use crate::claude_monitor::{
+    MonitorCommand, MonitorEvent, MonitorHandle, PauseReason, SharedToken, claude_usage_monitoring,
+};
+use crate::config::{
+    Config, CredentialBackend, LogLevel, PanelContent, UsageLevel, UsageThresholds,
+};
+use crate::credentials;
+use crate::error::{AppError, ErrorKind, SuggestedAction};
+use crate::fl;
+use crate::leader::{self, leader_election};
+use crate::logind::sleep_monitoring;
+use crate::notifications;
+use crate::poll_scheduler::WarningThresholds;
+use crate::power::{self, power_state_monitoring};
+use crate::shared_files_monitor::shared_files_monitoring;
+use crate::usage_cache::{self, UsageCache};
+use crate::usage_history::UsageHistory;
+use chrono::Datelike;
+use cosmic::cosmic_config::{self, CosmicConfigEntry};
+use cosmic::iced::{Alignment, Length, Limits, Subscription, window::Id};
 use cosmic::iced_winit::commands::popup::{destroy_popup, get_popup};
 use cosmic::prelude::*;
 use cosmic::widget;
+use std::time::Duration;
+
+/// Delay before the first attempt to save credentials again after a failure.
+const CREDENTIALS_SAVE_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Upper bound of the delay between attempts to save credentials.
+const CREDENTIALS_SAVE_MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+/// How often reset countdowns are updated while the popup or the details window is open.
+const COUNTDOWN_REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+/// How often the age of the usage data is checked while the popup is closed.
+const STALENESS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Opacity of usage data older than the staleness limit.
+const STALE_OPACITY: f32 = 0.5;
+/// Initial and minimum size of the details window.
+const DETAILS_WINDOW_SIZE: cosmic::iced::Size = cosmic::iced::Size::new(720.0, 640.0);
+const DETAILS_WINDOW_MIN_SIZE: cosmic::iced::Size = cosmic::iced::Size::new(480.0, 360.0);
+/// Number of columns in the usage history charts, and their height.
+const CHART_SLOTS: usize = 48;
+const CHART_HEIGHT: f32 = 80.0;
+
+/// Usage periods of the API response, named like its fields. The API doesn't tell
+/// what the `iguana_necktie` limits cover, only their windows.
+const PERIOD_NAMES: [&str; 7] = [
+    "five_hour",
+    "seven_day",
+    "seven_day_opus",
+    "seven_day_sonnet",
+    "seven_day_oauth_apps",
+    "iguana_necktie",
+    "seven_day_iguana_necktie",
+];
+/// Poll intervals offered in the settings, in seconds.
+const POLL_INTERVALS: [u64; 6] = [60, 120, 300, 600, 900, 1800];
+/// Panel contents offered in the settings.
+const PANEL_CONTENTS: [PanelContent; 4] = [
+    PanelContent::Icon,
+    PanelContent::Percentage,
+    PanelContent::IconAndPercentage,
+    PanelContent::Bar,
+];
+/// Thresholds offered in the settings, in percent, and their labels.
+const THRESHOLDS: [u32; 8] = [50, 60, 70, 75, 80, 85, 90, 95];
+const THRESHOLD_LABELS: [&str; 8] = ["50%", "60%", "70%", "75%", "80%", "85%", "90%", "95%"];
+/// Extra usage budgets offered in the settings, in cents.
+const EXTRA_USAGE_BUDGETS: [Option<u64>; 7] = [
+    None,
+    Some(1_000),
+    Some(2_500),
+    Some(5_000),
+    Some(10_000),
+    Some(25_000),
+    Some(50_000),
+];
+/// Credential backends offered in the settings.
+const CREDENTIAL_BACKENDS: [CredentialBackend; 2] =
+    [CredentialBackend::File, CredentialBackend::SecretService];
+/// Log levels offered in the settings.
+const LOG_LEVELS: [LogLevel; 5] = [
+    LogLevel::Error,
+    LogLevel::Warn,
+    LogLevel::Info,
+    LogLevel::Debug,
+    LogLevel::Trace,
+];
 
 /// The application model stores app-specific state used to describe its interface and
 /// drive its logic.
@@ -15,14 +97,77 @@ pub struct AppModel {
     core: cosmic::Core,
     /// The popup id.
     popup: Option<Id>,
+    /// Id of the details window, while it is open.
+    details_window: Option<Id>,
     /// Configuration data that persists between application runs.
-    /// Daily usage information
-    daily_usage: f32,
-    weekly_usage: f32,
+    config: Config,
+    /// Handle used to save configuration changes made in the settings.
+    config_handler: Option<cosmic_config::Config>,
+    /// Whether the popup shows the settings instead of the usage.
+    is_settings_open: bool,
+    /// Last usage data of the active profile.
+    usage: Option<claude::ClaudeUsageResponse>,
+    /// Usage of the active profile over the last day, drawn in the details window.
+    usage_history: UsageHistory,
+    /// Current time for the reset countdowns and the age of the usage data, updated
+    /// periodically.
+    now: chrono::DateTime<chrono::Utc>,
+    /// Moment of the last usage update received from the monitor.
+    usage_updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// How long the last successful usage request took.
+    usage_latency: Option<Duration>,
+    /// Whether this instance polls the API. The other instances read the usage cache.
+    is_leader: bool,
+    /// Command channel of the running usage monitor.
+    monitor: Option<MonitorHandle>,
+    /// Whether the monitor is waiting for a usage response.
+    is_fetching_usage: bool,
+    /// When the monitor will fetch usage data next, unless it is paused.
+    next_fetch_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Why the monitor is paused, if it is.
+    monitor_pause: Option<PauseReason>,
+    /// Whether the monitor stays paused until the token refresh in progress finishes.
+    is_resume_pending: bool,
+    /// Number of usage requests that failed since the last successful one.
+    usage_failures: u32,
+    /// Whether the last usage request failed because the API couldn't be reached.
+    is_offline: bool,
+    /// Power source and network cost, which decide the poll interval.
+    power_state: power::PowerState,
     /// Controls visibility of usage progress bars.
     is_usage_visible: bool,
-    /// Token for accessing the API.
-    access_token: claude::ClaudeCredentials,
+    /// Tokens for accessing the API, one profile per account.
+    credentials: credentials::CredentialStore,
+    /// Profile ids and labels shown in the profile switcher, in the same order.
+    profile_ids: Vec<String>,
+    profile_labels: Vec<String>,
+    /// Access token of the active profile, read by the running usage monitor.
+    monitor_token: SharedToken,
+    /// Whether a token refresh is in progress.
+    is_refreshing_token: bool,
+    /// Last budget alert sent and the month it was sent in, so every alert is sent
+    /// only once per month.
+    budget_alert: Option<((i32, u32), BudgetStatus)>,
+    /// Labels of the choices offered in the settings, in the same order as the choices,
+    /// translated for the user's locale.
+    period_labels: Vec<String>,
+    poll_interval_labels: Vec<String>,
+    panel_content_labels: Vec<String>,
+    extra_usage_budget_labels: Vec<String>,
+    credential_backend_labels: Vec<String>,
+    log_level_labels: Vec<String>,
+    /// Error shown in the popup until it is dismissed or resolved.
+    error: Option<AppError>,
+    /// Credential changes made in memory that aren't saved to disk yet, oldest first.
+    pending_credentials_changes: Vec<credentials::CredentialsChange>,
+    /// Whether the pending credential changes are being saved.
+    is_saving_credentials: bool,
+    /// Set while the credentials in memory couldn't be saved to disk.
+    credentials_save_error: Option<AppError>,
+    /// Number of failed attempts to save the credentials in a row.
+    credentials_save_attempts: u32,
+    /// Whether a background retry to save the credentials is scheduled.
+    is_credentials_save_scheduled: bool,
 }
 
 /// Messages emitted by the application and its widgets.
@@ -30,13 +175,47 @@ pub struct AppModel {
 pub enum Message {
     TogglePopup,
     PopupClosed(Id),
+    OpenDetails,
+    DetailsClosed(Id),
     LoginClicked,
     LoginCompleted(claude::AnthropicTokenResponse),
-    UpdateUsage(claude::ClaudeUsageResponse),
+    SelectProfile(usize),
+    Monitor(MonitorEvent),
+    BecameLeader,
+    UsageCacheChanged,
+    FetchRequested,
+    RefreshUsage,
     RefreshToken,
-    RefreshTokenCompleted(claude::AnthropicTokenResponse),
+    RefreshTokenCompleted(credentials::RefreshedCredentials),
+    RefreshTokenFailed(String),
     GetLocalCredentials,
-    ThrowError(String),
+    LocalCredentialsLoaded(Result<Option<credentials::CredentialStore>, String>),
+    CredentialsFileChanged,
+    CredentialsReloaded(Result<Option<credentials::CredentialStore>, String>),
+    PrepareForSleep(bool),
+    PowerStateChanged(power::PowerState),
+    ConnectivityRestored,
+    CredentialsSaved(usize, Result<credentials::CredentialStore, String>),
+    SaveCredentialsClicked,
+    SaveCredentialsRetry,
+    UpdateConfig(Config),
+    OpenSettings,
+    CloseSettings,
+    SetPollInterval(usize),
+    SetPeriodVisible(&'static str, bool),
+    SetPanelContent(usize),
+    SetPanelPeriod(usize),
+    SetWarningThreshold(usize),
+    SetCriticalThreshold(usize),
+    SetExtraUsageBudget(usize),
+    SetCredentialBackend(usize),
+    CredentialsMoved(CredentialBackend, Result<(), String>),
+    SetLogLevel(usize),
+    Tick,
+    Surface(cosmic::surface::Action),
+    CopyErrorDetails(String),
+    DismissError,
+    ThrowError(AppError),
 }
 
 /// Create a COSMIC application from the app model
@@ -66,12 +245,58 @@ impl cosmic::Application for AppModel {
         core: cosmic::Core,
         _flags: Self::Flags,
     ) -> (Self, Task<cosmic::Action<Self::Message>>) {
+        // Load the applet configuration, falling back to defaults for invalid entries.
+        let config_handler = cosmic_config::Config::new(Self::APP_ID, Config::VERSION)
+            .inspect_err(|why| log::error!("error opening config: {why}"))
+            .ok();
+        let config = config_handler
+            .as_ref()
+            .map(|context| match Config::get_entry(context) {
+                Ok(config) => config,
+                Err((errors, config)) => {
+                    for why in errors {
+                        log::error!("error loading config: {why}");
+                    }
+                    config
+                }
+            })
+            .unwrap_or_default();
+
+        log::set_max_level(config.log_level.into());
+
         // Construct the app model with the runtime's core.
         let app = AppModel {
             core,
-            daily_usage: 0.0,
-            weekly_usage: 0.0,
+            config,
+            config_handler,
+            now: chrono::Utc::now(),
             is_usage_visible: false,
+            period_labels: PERIOD_NAMES.into_iter().map(period_label).collect(),
+            poll_interval_labels: POLL_INTERVALS
+                .iter()
+                .map(|poll_interval| fl!("minutes", minutes = poll_interval / 60))
+                .collect(),
+            panel_content_labels: vec![
+                fl!("panel-content-icon"),
+                fl!("panel-content-percentage"),
+                fl!("panel-content-icon-and-percentage"),
+                fl!("panel-content-bar"),
+            ],
+            extra_usage_budget_labels: EXTRA_USAGE_BUDGETS
+                .iter()
+                .map(|budget| budget.map_or_else(|| fl!("budget-none"), budget::format_credits))
+                .collect(),
+            credential_backend_labels: vec![
+                fl!("credential-backend-file"),
+                fl!("credential-backend-secret-service"),
+            ],
+            log_level_labels: vec![
+                fl!("log-level-error"),
+                fl!("log-level-warn"),
+                fl!("log-level-info"),
+                fl!("log-level-debug"),
+                fl!("log-level-trace"),
+            ],
             ..Default::default()
         };
 
@@ -82,7 +307,11 @@ impl cosmic::Application for AppModel {
     }
 
     fn on_close_requested(&self, id: Id) -> Option<Message> {
-        Some(Message::PopupClosed(id))
+        if self.details_window == Some(id) {
+            Some(Message::DetailsClosed(id))
+        } else {
+            Some(Message::PopupClosed(id))
+        }
     }
 
     /// Describes the interface based on the current state of the application model.
@@ -91,41 +320,226 @@ impl cosmic::Application for AppModel {
     /// This view should emit messages to toggle the applet's popup window, which will
     /// be drawn using the `view_window` method.
     fn view(&self) -> Element<'_, Self::Message> {
-        self.core
-            .applet
-            .icon_button(Self::APP_ID)
-            .on_press(Message::TogglePopup)
-            .into()
+        let applet = &self.core.applet;
+
+        let panel_usage = self.panel_usage();
+        let utilization = panel_usage.map(|(_, utilization)| utilization);
+        let level = panel_usage.map_or(UsageLevel::Normal, |(period, utilization)| {
+            self.config.thresholds(period).level(utilization)
+        });
+        let is_stale = self.is_usage_stale();
+        let color = usage_color(level, is_stale);
+
+        if self.config.panel_content == PanelContent::Icon && color.is_none() {
+            return self.hover_tooltip(
+                applet
+                    .icon_button(Self::APP_ID)
+                    .on_press(Message::TogglePopup),
+            );
+        }
+
+        let is_horizontal = applet.is_horizontal();
+        let (icon_size, _) = applet.suggested_size(true);
+
+        let icon = || {
+            let icon = widget::icon::from_name(Self::APP_ID)
+                .size(icon_size)
+                .symbolic(true)
+                .icon();
+
+            match color {
+                Some(color) => icon.class(cosmic::theme::Svg::custom(move |_| {
+                    cosmic::iced::widget::svg::Style { color: Some(color) }
+                })),
+                None => icon,
+            }
+        };
+
+        let percentage = || {
+            let text = applet.text(utilization.map_or_else(
+                || "--%".to_string(),
+                |utilization| format!("{utilization:.0}%"),
+            ));
+
+            match color {
+                Some(color) => text.class(cosmic::theme::Text::Color(color)),
+                None => text,
+            }
+        };
+
+        let content: Element<'_, Self::Message> = match self.config.panel_content {
+            PanelContent::Icon => icon().into(),
+            PanelContent::Percentage => percentage().into(),
+            PanelContent::IconAndPercentage => {
+                if is_horizontal {
+                    widget::row()
+                        .spacing(4)
+                        .align_y(Alignment::Center)
+                        .push(icon())
+                        .push(percentage())
+                        .into()
+                } else {
+                    widget::column()
+                        .spacing(2)
+                        .align_x(Alignment::Center)
+                        .push(icon())
+                        .push(percentage())
+                        .into()
+                }
+            }
+            PanelContent::Bar => {
+                // Wide and thin along a horizontal panel, as wide as the icon otherwise.
+                let width = if is_horizontal {
+                    icon_size * 3
+                } else {
+                    icon_size
+                };
+
+                widget::container(
+                    widget::progress_bar(0.0..=1.0, utilization.unwrap_or(0.0) / 100.0)
+                        .width(f32::from(width))
+                        .height(6.0)
+                        .class(usage_bar_class(level, is_stale)),
+                )
+                .center_y(f32::from(icon_size))
+                .into()
+            }
+        };
+
+        let padding = applet.suggested_padding(true);
+        let button = widget::button::custom(content)
+            .padding(if is_horizontal {
+                [0, padding]
+            } else {
+                [padding, 0]
+            })
+            .class(cosmic::theme::Button::AppletIcon)
+            .on_press(Message::TogglePopup);
+
+        applet.autosize_window(self.hover_tooltip(button)).into()
     }
 
     /// The applet's popup window will be drawn using this view method. If there are
     /// multiple poups, you may match the id parameter to determine which popup to
     /// create a view for.
-    fn view_window(&self, _id: Id) -> Element<'_, Self::Message> {
+    fn view_window(&self, id: Id) -> Element<'_, Self::Message> {
+        if self.details_window == Some(id) {
+            return self.details_view();
+        }
+
+        if self.is_settings_open {
+            return self
+                .core
+                .applet
+                .popup_container(self.settings_view())
+                .into();
+        }
+
         let mut content_list = widget::list_column().padding(2);
 
+        for error in self.error.iter().chain(&self.credentials_save_error) {
+            content_list = content_list.add(error_banner(error));
+        }
+
         if self.is_usage_visible {
+            content_list = content_list.add(widget::container(
+                widget::row()
+                    .spacing(8)
+                    .align_y(Alignment::Center)
+                    .push(
+                        widget::dropdown(
+                            &self.profile_labels,
+                            self.active_profile_index(),
+                            Message::SelectProfile,
+                        )
+                        .width(Length::Fill),
+                    )
+                    .push(widget::button::text(fl!("add-account")).on_press(Message::LoginClicked))
+                    .push(
+                        widget::button::icon(widget::icon::from_name("view-refresh-symbolic"))
+                            .on_press(Message::RefreshUsage),
+                    )
+                    .push(
+                        widget::button::icon(widget::icon::from_name("emblem-system-symbolic"))
+                            .on_press(Message::OpenSettings),
+                    ),
+            ));
+
+            if self.usage_failures > 0 {
+                content_list = content_list.add(widget::container(widget::text::caption(
+                    self.stale_usage_notice(),
+                )));
+            }
+
+            if let Some(updated_at) = self.usage_updated_at {
+                content_list = content_list.add(widget::container(widget::text::caption(
+                    time_since_update(updated_at, self.now),
+                )));
+            }
+
+            if let Some(usage) = &self.usage {
+                let is_stale = self.is_usage_stale();
+
+                // One section per usage period the API reported.
+                let visible_periods = usage.periods().into_iter().filter(|(name, _)| {
+                    !self
+                        .config
+                        .hidden_periods
+                        .iter()
+                        .any(|hidden| hidden == name)
+                });
+
+                for (name, period) in visible_periods {
+                    content_list = content_list.add(usage_section(
+                        period_label(name),
+                        period.utilization,
+                        self.config.thresholds(name).level(period.utilization),
+                        is_stale,
+                        period
+                            .resets_at
+                            .map(|resets_at| reset_countdown(resets_at, self.now)),
+                    ));
+                }
+
+                if usage.extra_usage.is_enabled
+                    && !self
+                        .config
+                        .hidden_periods
+                        .iter()
+                        .any(|hidden| hidden == "extra_usage")
+                {
+                    content_list =
+                        content_list.add(self.extra_usage_section(&usage.extra_usage, is_stale));
+                }
+            } else {
+                content_list = content_list.add(widget::container(widget::text::caption(fl!(
+                    "no-usage-data"
+                ))));
+            }
+
             content_list = content_list.add(widget::container(
                 widget::column()
                     .spacing(2)
                     .padding(2)
-                    // Daily usage progress bar
-                    .push(widget::text("Daily usage"))
-                    .push(widget::progress_bar(0.0..=1.0, self.daily_usage / 100.0).height(6.0))
-                    .push(widget::text(format!("{:.0}%", self.daily_usage)))
-                    // Weekly usage progress bar
-                    .push(widget::text("Weekly usage"))
-                    .push(widget::progress_bar(0.0..=1.0, self.weekly_usage / 100.0).height(6.0))
-                    .push(widget::text(format!("{:.0}%", self.weekly_usage))),
+                    .push(widget::text::heading(fl!("diagnostics")))
+                    .push(widget::text::caption(self.polling_policy()))
+                    .push(widget::text::caption(self.monitor_status())),
+            ));
+
+            content_list = content_list.add(widget::container(
+                widget::button::text(fl!("open-details")).on_press(Message::OpenDetails),
             ));
         } else {
             content_list = content_list.add(widget::container(
-                widget::column().spacing(10).push(
-                    widget::button::standard("Login")
-                        .width(Length::Fill)
-                        .height(40)
-                        .on_press(Message::LoginClicked),
-                ),
+                widget::column()
+                    .spacing(10)
+                    .push(
+                        widget::button::standard(fl!("login"))
+                            .width(Length::Fill)
+                            .height(40)
+                            .on_press(Message::LoginClicked),
+                    )
+                    .push(widget::button::text(fl!("settings")).on_press(Message::OpenSettings)),
             ));
         }
 
@@ -140,25 +554,94 @@ impl cosmic::Application for AppModel {
     /// continue to execute for the duration that they remain in the batch.
     fn subscription(&self) -> Subscription<Self::Message> {
         struct UsageMonitor;
+        struct SharedFilesMonitor;
+        struct SleepMonitor;
+        struct PowerStateMonitor;
+        struct LeaderElection;
 
-        let mut subscriptions = vec![];
+        let mut subscriptions = vec![
+            Subscription::run_with_id(
+                std::any::TypeId::of::<SharedFilesMonitor>(),
+                cosmic::iced::stream::channel(1, |mut channel| async move {
+                    shared_files_monitoring(&mut channel).await;
+                }),
+            ),
+            Subscription::run_with_id(
+                std::any::TypeId::of::<LeaderElection>(),
+                cosmic::iced::stream::channel(1, |mut channel| async move {
+                    leader_election(&mut channel).await;
+                }),
+            ),
+            Subscription::run_with_id(
+                std::any::TypeId::of::<SleepMonitor>(),
+                cosmic::iced::stream::channel(1, |mut channel| async move {
+                    sleep_monitoring(&mut channel).await;
+                }),
+            ),
+            Subscription::run_with_id(
+                std::any::TypeId::of::<PowerStateMonitor>(),
+                cosmic::iced::stream::channel(1, |mut channel| async move {
+                    power_state_monitoring(&mut channel).await;
+                }),
+            ),
+            // Watch for changes to the applet configuration.
+            self.core()
+                .watch_config::<Config>(Self::APP_ID)
+                .map(|update| {
+                    for why in update.errors {
+                        log::error!("error reloading config: {why}");
+                    }
+                    Message::UpdateConfig(update.config)
+                }),
+        ];
 
-        // Only run monitoring subscription if user is logged in
-        if self.is_usage_visible && !self.access_token.access_token.is_empty() {
-            let access_token = self.access_token.clone();
+        // Only run monitoring subscription if user is logged in and no other instance
+        // polls. It is keyed by the active profile, so switching accounts restarts it
+        // right away. Refreshed tokens and poll interval changes reach the running
+        // monitor instead.
+        if self.is_usage_visible
+            && self.is_leader
+            && let Some(profile_id) = self.credentials.active_profile.clone()
+            && self
+                .credentials
+                .active()
+                .is_some_and(|credentials| !credentials.access_token.is_empty())
+        {
+            let poll_interval = self.config.poll_interval(self.power_state);
+            let warning_thresholds = WarningThresholds::from(&self.config);
+            let monitor_token = self.monitor_token.clone();
 
             subscriptions.push(Subscription::run_with_id(
-                std::any::TypeId::of::<UsageMonitor>(),
+                (std::any::TypeId::of::<UsageMonitor>(), profile_id),
                 cosmic::iced::stream::channel(10, move |mut channel| {
-                    let token = access_token.access_token.clone();
+                    let token = monitor_token.clone();
+                    let warning_thresholds = warning_thresholds.clone();
 
                     async move {
-                        claude_usage_monitoring(token, &mut channel).await;
+                        claude_usage_monitoring(
+                            token,
+                            poll_interval,
+                            warning_thresholds,
+                            &mut channel,
+                        )
+                        .await;
                     }
                 }),
             ));
         }
 
+        // Keep the reset countdowns current while they are visible, and notice when
+        // the usage data gets too old.
+        if self.is_usage_visible {
+            let interval = if self.popup.is_some() || self.details_window.is_some() {
+                COUNTDOWN_REFRESH_INTERVAL
+            } else {
+                STALENESS_CHECK_INTERVAL
+            };
+
+            subscriptions.push(cosmic::iced::time::every(interval).map(|_| Message::Tick));
+        }
+
         Subscription::batch(subscriptions)
     }
 
@@ -171,18 +654,104 @@ impl cosmic::Application for AppModel {
         match message {
             Message::GetLocalCredentials => {
                 log::info!("checking for local credentials");
-                match claude::get_local_credentials() {
-                    Ok(credentials) => {
+                return Task::perform(
+                    credentials::load_credentials(self.config.credential_backend),
+                    |result| cosmic::Action::App(Message::LocalCredentialsLoaded(result)),
+                );
+            }
+            Message::LocalCredentialsLoaded(result) => {
+                match result {
+                    Ok(Some(store)) => {
                         log::info!("local credentials found, logging in automatically");
-                        self.access_token = credentials;
-                        self.is_usage_visible = true;
+                        self.is_usage_visible = store.active().is_some();
+                        self.credentials = store;
+                        self.sync_profiles();
+
+                        // Show the last usage published by any instance until the
+                        // next fetch.
+                        self.load_usage_cache();
                     }
+                    // Nothing stored only means nobody logged in yet.
+                    Ok(None) => log::debug!("no local credentials found"),
                     Err(error) => {
-                        log::debug!("no local credentials found: {error}");
-                        let _ = cosmic::Action::App(Message::ThrowError(error));
+                        return self.update(Message::ThrowError(AppError::new(
+                            ErrorKind::Credentials,
+                            error,
+                        )));
                     }
                 }
             }
+            Message::CredentialsFileChanged => {
+                log::info!("stored credentials changed, reloading credentials");
+                return Task::perform(
+                    credentials::load_credentials(self.config.credential_backend),
+                    |result| cosmic::Action::App(Message::CredentialsReloaded(result)),
+                );
+            }
+            Message::CredentialsReloaded(result) => {
+                let store = match result {
+                    Ok(Some(store)) => store,
+                    Ok(None) => {
+                        log::warn!("keeping in-memory credentials: nothing stored");
+                        return Task::none();
+                    }
+                    Err(error) => {
+                        log::warn!("keeping in-memory credentials: {error}");
+                        return Task::none();
+                    }
+                };
+
+                let previous_token = self
+                    .credentials
+                    .active()
+                    .map(|credentials| credentials.access_token.clone());
+                // Changes not saved yet stay on top of the stored copy.
+                let store = self.with_pending_changes(store);
+                let current_token = store
+                    .active()
+                    .map(|credentials| credentials.access_token.clone());
+
+                self.is_usage_visible = store.active().is_some();
+                self.set_credentials(store);
+
+                if previous_token != current_token {
+                    log::info!("credentials replaced externally, fetching usage with new token");
+                    self.send_to_monitor(MonitorCommand::FetchNow);
+                }
+            }
+            Message::PrepareForSleep(true) => {
+                self.send_to_monitor(MonitorCommand::Pause(PauseReason::Suspended));
+            }
+            Message::PrepareForSleep(false) => {
+                // The access token may have expired while the system was suspended. The
+                // monitor is resumed once the refresh finishes. Only the polling
+                // instance refreshes tokens.
+                if self.is_leader
+                    && self
+                        .credentials
+                        .active()
+                        .is_some_and(credentials::ClaudeCredentials::is_expired)
+                {
+                    log::info!("access token expired during suspend");
+                    self.is_resume_pending = true;
+                    return self.update(Message::RefreshToken);
+                }
+
+                self.send_to_monitor(MonitorCommand::Resume);
+            }
+            Message::ConnectivityRestored => {
+                // The next retry can be up to a poll interval away.
+                if self.is_offline {
+                    log::info!("back online, fetching usage");
+                    self.send_to_monitor(MonitorCommand::FetchNow);
+                }
+            }
+            Message::PowerStateChanged(power_state) => {
+                self.power_state = power_state;
+                self.send_to_monitor(MonitorCommand::SetPollInterval(
+                    self.config.poll_interval(self.power_state),
+                ));
+            }
             Message::LoginClicked => {
                 log::info!("login button clicked, starting oauth flow");
                 return Task::perform(claude::open_oauth_login(), |oauth_response| {
@@ -190,61 +759,262 @@ impl cosmic::Application for AppModel {
                         Ok(authorization) => {
                             cosmic::Action::App(Message::LoginCompleted(authorization))
                         }
-                        Err(error) => cosmic::Action::App(Message::ThrowError(error)),
+                        Err(error) => cosmic::Action::App(Message::ThrowError(AppError::new(
+                            ErrorKind::Login,
+                            error,
+                        ))),
                     }
                 });
             }
             Message::LoginCompleted(authorization) => {
                 log::info!("login completed successfully, saving credentials");
-                let _ = claude::save_credentials_locally(&authorization);
-
-                self.access_token = claude::ClaudeCredentials {
-                    access_token: authorization.access_token,
-                    refresh_token: authorization.refresh_token,
-                };
+                self.error = None;
                 self.is_usage_visible = true;
                 log::info!("user authenticated, monitoring will start");
+
+                return self.change_credentials(credentials::CredentialsChange::Login(
+                    credentials::ClaudeCredentials::from(authorization),
+                ));
+            }
+            Message::SelectProfile(index) => {
+                let Some(profile_id) = self.profile_ids.get(index).cloned() else {
+                    return Task::none();
+                };
+
+                if self.credentials.active_profile.as_ref() == Some(&profile_id) {
+                    return Task::none();
+                }
+
+                log::info!("switching to profile {profile_id}");
+
+                return self
+                    .change_credentials(credentials::CredentialsChange::SetActive(profile_id));
             }
             Message::RefreshToken => {
-                log::info!("refreshing token started");
+                // The monitor keeps asking for a refresh on every failed poll.
+                if self.is_refreshing_token {
+                    log::debug!("token refresh already in progress");
+                    return Task::none();
+                }
 
-                self.is_usage_visible = false;
-                let refresh_token = self.access_token.refresh_token.clone();
+                let Some(profile_id) = self.credentials.active_profile.clone() else {
+                    return Task::none();
+                };
+
+                log::info!("refreshing token started");
+                self.is_refreshing_token = true;
 
                 return Task::perform(
-                    claude::refresh_credentials(refresh_token),
-                    |refreshed_token| match refreshed_token {
-                        Ok(new_credentials) => {
-                            cosmic::Action::App(Message::RefreshTokenCompleted(new_credentials))
-                        }
-                        Err(error) => cosmic::Action::App(Message::ThrowError(error)),
+                    credentials::refresh_profile_credentials(
+                        self.config.credential_backend,
+                        self.credentials.clone(),
+                        profile_id,
+                    ),
+                    |refreshed_store| match refreshed_store {
+                        Ok(store) => cosmic::Action::App(Message::RefreshTokenCompleted(store)),
+                        Err(error) => cosmic::Action::App(Message::RefreshTokenFailed(error)),
                     },
                 );
             }
-            Message::RefreshTokenCompleted(new_credentials) => {
-                log::info!("token refreshed successfully, saving new credentials");
-                let _ = claude::save_credentials_locally(&new_credentials);
+            Message::RefreshTokenCompleted(refreshed) => {
+                // The refreshed credentials were already saved while holding the lock,
+                // unless saving failed. Then they are saved like any other change.
+                log::info!("token refreshed successfully");
+                self.is_refreshing_token = false;
+                self.error
+                    .take_if(|error| error.kind == ErrorKind::TokenRefresh);
 
-                self.access_token = claude::ClaudeCredentials {
-                    access_token: new_credentials.access_token,
-                    refresh_token: new_credentials.refresh_token,
+                if let (Some(change), Some(_)) = (refreshed.change, refreshed.save_error) {
+                    self.pending_credentials_changes.push(change);
+                }
+
+                let store = self.with_pending_changes(refreshed.store);
+                self.set_credentials(store);
+
+                self.is_usage_visible = self.credentials.active().is_some();
+                log::info!("token refreshed, fetching usage with the new token");
+                // A paused monitor ignores fetch requests, resuming it fetches right away.
+                if std::mem::take(&mut self.is_resume_pending) {
+                    self.send_to_monitor(MonitorCommand::Resume);
+                } else {
+                    self.send_to_monitor(MonitorCommand::FetchNow);
+                }
+
+                return self.save_credentials();
+            }
+            Message::RefreshTokenFailed(error) => {
+                self.is_refreshing_token = false;
+
+                // Don't leave the monitor paused if the refresh was started on resume.
+                self.is_resume_pending = false;
+                self.send_to_monitor(MonitorCommand::Resume);
+
+                return self.update(Message::ThrowError(AppError::new(
+                    ErrorKind::TokenRefresh,
+                    error,
+                )));
+            }
+            Message::CredentialsSaved(saved, result) => {
+                self.is_saving_credentials = false;
+
+                let store = match result {
+                    Ok(store) => store,
+                    Err(error) => return self.credentials_save_failed(error),
                 };
-                self.is_usage_visible = true;
-                log::info!("token refreshed, monitoring will start");
+
+                self.pending_credentials_changes.drain(..saved);
+
+                if self.credentials_save_error.take().is_some() {
+                    log::info!(
+                        "credentials saved after {} failed attempt(s)",
+                        self.credentials_save_attempts
+                    );
+                }
+
+                self.credentials_save_attempts = 0;
+
+                // The saved store also holds the changes of other processes.
+                let store = self.with_pending_changes(store);
+                self.set_credentials(store);
+
+                // Save changes made while this save was running.
+                return self.save_credentials();
+            }
+            Message::SaveCredentialsClicked => {
+                return self.save_credentials();
+            }
+            Message::SaveCredentialsRetry => {
+                self.is_credentials_save_scheduled = false;
+                return self.save_credentials();
+            }
+            Message::Monitor(MonitorEvent::Ready(monitor)) => {
+                log::debug!("usage monitor ready to receive commands");
+                self.monitor = Some(monitor);
+
+                // A new monitor replaces one that may have stopped in any state.
+                self.is_fetching_usage = false;
+                self.next_fetch_at = None;
+                self.monitor_pause = None;
             }
-            Message::UpdateUsage(usage_data) => {
+            Message::Monitor(MonitorEvent::FetchStarted) => {
+                self.is_fetching_usage = true;
+            }
+            Message::Monitor(MonitorEvent::FetchSucceeded {
+                usage,
+                at,
+                latency,
+                next_fetch_in,
+            }) => {
                 log::debug!(
-                    "updating ui with usage data: daily={:.0}%, weekly={:.0}%",
-                    usage_data.five_hour.utilization,
-                    usage_data.seven_day.utilization
+                    "updating ui with usage data: five_hour={:.0}%, seven_day={:.0}%",
+                    usage.five_hour.utilization,
+                    usage.seven_day.utilization
                 );
-                self.daily_usage = usage_data.five_hour.utilization;
-                self.weekly_usage = usage_data.seven_day.utilization;
+                self.apply_usage(&usage, at);
+                self.usage_latency = Some(latency);
+                self.is_fetching_usage = false;
+                self.next_fetch_at = chrono::Duration::from_std(next_fetch_in)
+                    .ok()
+                    .map(|next_fetch_in| at + next_fetch_in);
+
+                if self.is_offline {
+                    log::info!("usage api reachable again");
+                }
+                self.usage_failures = 0;
+                self.is_offline = false;
+                self.error.take_if(|error| {
+                    matches!(error.kind, ErrorKind::Usage | ErrorKind::TokenRefresh)
+                });
+
+                // Publish the usage to the instances that don't poll.
+                if let Some(profile_id) = self.credentials.active_profile.clone() {
+                    let cache = UsageCache {
+                        profile_id,
+                        fetched_at: at,
+                        usage: *usage,
+                    };
+
+                    if let Err(error) = usage_cache::save_usage_cache(&cache) {
+                        log::warn!("usage cache not saved: {error}");
+                    }
+                }
+
+                return self.check_budget();
+            }
+            Message::Monitor(MonitorEvent::FetchFailed {
+                error,
+                request_id,
+                is_offline,
+                is_auth_expired,
+                failures,
+                retry_in,
+            }) => {
+                if is_offline && !self.is_offline {
+                    log::info!("usage api unreachable, marking usage data as stale");
+                }
+                self.usage_failures = failures;
+                self.is_offline = is_offline;
+                self.is_fetching_usage = false;
+                self.next_fetch_at = chrono::Duration::from_std(retry_in)
+                    .ok()
+                    .map(|retry_in| chrono::Utc::now() + retry_in);
+
+                // Expired tokens are refreshed without bothering the user, and being
+                // offline is already shown next to the stale usage.
+                if is_auth_expired {
+                    return self.update(Message::RefreshToken);
+                }
+
+                if !is_offline {
+                    return self.update(Message::ThrowError(
+                        AppError::new(ErrorKind::Usage, error).with_request_id(request_id),
+                    ));
+                }
+            }
+            Message::Monitor(MonitorEvent::Paused(reason)) => {
+                self.monitor_pause = Some(reason);
+                self.next_fetch_at = None;
+            }
+            Message::Monitor(MonitorEvent::Resumed) => {
+                self.monitor_pause = None;
+            }
+            Message::BecameLeader => {
+                log::info!("polling usage from this instance");
+                self.is_leader = true;
+            }
+            Message::UsageCacheChanged => {
+                // The polling instance already shows what it wrote to the cache.
+                if !self.is_leader {
+                    self.load_usage_cache();
+                }
+            }
+            Message::RefreshUsage => {
+                self.request_fetch();
+            }
+            Message::FetchRequested => {
+                if self.is_leader {
+                    log::debug!("usage fetch requested by another applet instance");
+                    self.send_to_monitor(MonitorCommand::FetchNow);
+                }
             }
             Message::TogglePopup => {
                 return if let Some(p) = self.popup.take() {
+                    self.is_settings_open = false;
                     destroy_popup(p)
                 } else {
+                    let is_usage_stale = self.usage_updated_at.is_none_or(|updated_at| {
+                        chrono::Utc::now()
+                            .signed_duration_since(updated_at)
+                            .num_seconds()
+                            >= i64::try_from(self.config.refresh_on_open_after).unwrap_or(i64::MAX)
+                    });
+
+                    if is_usage_stale {
+                        self.request_fetch();
+                    }
+
+                    self.now = chrono::Utc::now();
+
                     let new_id = Id::unique();
                     self.popup.replace(new_id);
                     let mut popup_settings = self.core.applet.get_popup_settings(
@@ -265,10 +1035,192 @@ impl cosmic::Application for AppModel {
             Message::PopupClosed(id) => {
                 if self.popup.as_ref() == Some(&id) {
                     self.popup = None;
+                    self.is_settings_open = false;
+                }
+            }
+            Message::OpenDetails => {
+                if let Some(id) = self.details_window {
+                    return cosmic::iced::window::gain_focus(id);
+                }
+
+                let (id, open) = cosmic::iced::window::open(cosmic::iced::window::Settings {
+                    size: DETAILS_WINDOW_SIZE,
+                    min_size: Some(DETAILS_WINDOW_MIN_SIZE),
+                    resizable: true,
+                    ..Default::default()
+                });
+                self.details_window = Some(id);
+                self.now = chrono::Utc::now();
+
+                // The window takes over from the popup.
+                let close_popup = match self.popup.take() {
+                    Some(popup) => {
+                        self.is_settings_open = false;
+                        destroy_popup(popup)
+                    }
+                    None => Task::none(),
+                };
+
+                return Task::batch([close_popup, open.discard()]);
+            }
+            Message::DetailsClosed(id) => {
+                if self.details_window == Some(id) {
+                    self.details_window = None;
+                    return cosmic::iced::window::close(id);
                 }
             }
+            Message::UpdateConfig(config) => {
+                log::debug!("config updated: {config:?}");
+                let is_backend_changed =
+                    self.config.credential_backend != config.credential_backend;
+                self.config = config;
+                log::set_max_level(self.config.log_level.into());
+                self.send_to_monitor(MonitorCommand::SetPollInterval(
+                    self.config.poll_interval(self.power_state),
+                ));
+                self.send_to_monitor(MonitorCommand::SetWarningThresholds(
+                    WarningThresholds::from(&self.config),
+                ));
+
+                // The credentials were moved, by this instance or another one.
+                if is_backend_changed {
+                    return self.update(Message::CredentialsFileChanged);
+                }
+            }
+            Message::OpenSettings => {
+                self.is_settings_open = true;
+            }
+            Message::CloseSettings => {
+                self.is_settings_open = false;
+            }
+            Message::SetPollInterval(index) => {
+                if let Some(poll_interval) = POLL_INTERVALS.get(index).copied() {
+                    self.update_config(|config, handler| {
+                        config.set_poll_interval(handler, poll_interval)
+                    });
+                }
+            }
+            Message::SetPeriodVisible(name, is_visible) => {
+                let mut hidden_periods = self.config.hidden_periods.clone();
+                hidden_periods.retain(|hidden| hidden != name);
+
+                if !is_visible {
+                    hidden_periods.push(name.to_string());
+                }
+
+                self.update_config(|config, handler| {
+                    config.set_hidden_periods(handler, hidden_periods)
+                });
+            }
+            Message::SetPanelContent(index) => {
+                if let Some(panel_content) = PANEL_CONTENTS.get(index).copied() {
+                    self.update_config(|config, handler| {
+                        config.set_panel_content(handler, panel_content)
+                    });
+                }
+            }
+            Message::SetPanelPeriod(index) => {
+                if let Some(name) = PERIOD_NAMES.get(index) {
+                    self.update_config(|config, handler| {
+                        config.set_panel_period(handler, name.to_string())
+                    });
+                }
+            }
+            Message::SetWarningThreshold(index) => {
+                // The critical threshold moves up with the warning one to stay above
+                // it. The highest threshold can't be a warning.
+                let warning = THRESHOLDS.get(index).copied();
+                let critical = self.config.default_thresholds.critical;
+                let critical = match warning {
+                    Some(warning) if critical <= warning => THRESHOLDS.get(index + 1).copied(),
+                    _ => Some(critical),
+                };
+
+                if let (Some(warning), Some(critical)) = (warning, critical) {
+                    let thresholds = UsageThresholds { warning, critical };
+
+                    self.update_config(|config, handler| {
+                        config.set_default_thresholds(handler, thresholds)
+                    });
+                }
+            }
+            Message::SetCriticalThreshold(index) => {
+                // The warning threshold moves down with the critical one to stay below
+                // it. The lowest threshold can't be critical.
+                let critical = THRESHOLDS.get(index).copied();
+                let warning = self.config.default_thresholds.warning;
+                let warning = match critical {
+                    Some(critical) if warning >= critical => index
+                        .checked_sub(1)
+                        .and_then(|index| THRESHOLDS.get(index).copied()),
+                    _ => Some(warning),
+                };
+
+                if let (Some(warning), Some(critical)) = (warning, critical) {
+                    let thresholds = UsageThresholds { warning, critical };
+
+                    self.update_config(|config, handler| {
+                        config.set_default_thresholds(handler, thresholds)
+                    });
+                }
+            }
+            Message::SetExtraUsageBudget(index) => {
+                if let Some(budget) = EXTRA_USAGE_BUDGETS.get(index).copied() {
+                    self.update_config(|config, handler| {
+                        config.set_extra_usage_budget(handler, budget)
+                    });
+                }
+            }
+            Message::SetCredentialBackend(index) => {
+                let from = self.config.credential_backend;
+
+                if let Some(to) = CREDENTIAL_BACKENDS.get(index).copied()
+                    && to != from
+                {
+                    log::info!("moving credentials from {from:?} to {to:?}");
+                    return Task::perform(
+                        credentials::move_credentials(from, to, self.credentials.clone()),
+                        move |result| cosmic::Action::App(Message::CredentialsMoved(to, result)),
+                    );
+                }
+            }
+            Message::CredentialsMoved(backend, result) => {
+                // The config only points to the new backend once the credentials are
+                // there, so every instance keeps finding them.
+                match result {
+                    Ok(()) => self.update_config(|config, handler| {
+                        config.set_credential_backend(handler, backend)
+                    }),
+                    Err(error) => {
+                        return self.update(Message::ThrowError(AppError::new(
+                            ErrorKind::CredentialsMove,
+                            error,
+                        )));
+                    }
+                }
+            }
+            Message::SetLogLevel(index) => {
+                if let Some(log_level) = LOG_LEVELS.get(index).copied() {
+                    self.update_config(|config, handler| config.set_log_level(handler, log_level));
+                }
+            }
+            Message::Tick => {
+                self.now = chrono::Utc::now();
+            }
+            Message::Surface(action) => {
+                return cosmic::task::message(cosmic::Action::Cosmic(
+                    cosmic::app::Action::Surface(action),
+                ));
+            }
+            Message::CopyErrorDetails(report) => {
+                return cosmic::iced::clipboard::write(report);
+            }
+            Message::DismissError => {
+                self.error = None;
+            }
             Message::ThrowError(error) => {
-                log::error!("error occurred: {error}");
+                log::error!("error occurred: {}", error.details);
+                self.error = Some(error);
             }
         }
         Task::none()
@@ -278,3 +1230,1101 @@ impl cosmic::Application for AppModel {
         Some(cosmic::applet::style())
     }
 }
+
+impl AppModel {
+    /// Name and utilization of the usage period chosen for the panel. Falls back to the
+    /// 5-hour session if the response doesn't include that period.
+    fn panel_usage(&self) -> Option<(&'static str, f32)> {
+        let usage = self.usage.as_ref()?;
+
+        let panel_usage = usage
+            .periods()
+            .into_iter()
+            .find(|(name, _)| *name == self.config.panel_period)
+            .map_or(
+                ("five_hour", usage.five_hour.utilization),
+                |(name, period)| (name, period.utilization),
+            );
+
+        Some(panel_usage)
+    }
+
+    /// Credits spent on extra usage this month, with the spend projected for the end of
+    /// the month and the soft budget.
+    fn extra_usage_section(
+        &self,
+        extra_usage: &claude::ExtraUsage,
+        is_stale: bool,
+    ) -> Element<'_, Message> {
+        let used_credits = extra_usage.used_credits.unwrap_or(0);
+        let projected = budget::projected_month_spend(used_credits, self.now.into());
+
+        let utilization = extra_usage
+            .utilization
+            .or_else(|| {
+                extra_usage
+                    .monthly_limit
+                    .filter(|monthly_limit| *monthly_limit > 0)
+                    .map(|monthly_limit| used_credits as f32 / monthly_limit as f32 * 100.0)
+            })
+            .unwrap_or(0.0);
+
+        let spent = match extra_usage.monthly_limit {
+            Some(monthly_limit) => fl!(
+                "extra-usage-spent-of-limit",
+                spent = budget::format_credits(used_credits),
+                limit = budget::format_credits(monthly_limit)
+            ),
+            None => fl!(
+                "extra-usage-spent",
+                spent = budget::format_credits(used_credits)
+            ),
+        };
+
+        let mut section = widget::column()
+            .spacing(2)
+            .padding(2)
+            .push(widget::text(fl!("extra-usage")))
+            .push(
+                widget::progress_bar(0.0..=1.0, utilization / 100.0)
+                    .height(6.0)
+                    .class(usage_bar_class(
+                        self.config.thresholds("extra_usage").level(utilization),
+                        is_stale,
+                    )),
+            )
+            .push(widget::text(spent));
+
+        if let Some(projected) = projected {
+            section = section.push(widget::text::caption(fl!(
+                "extra-usage-projected",
+                projected = budget::format_credits(projected)
+            )));
+        }
+
+        if let Some(budget) = self.config.extra_usage_budget {
+            let status = budget::budget_status(used_credits, projected, budget);
+            let budget = budget::format_credits(budget);
+
+            let (notice, level) = match status {
+                BudgetStatus::WithinBudget => (
+                    fl!("budget-within", budget = budget.as_str()),
+                    UsageLevel::Normal,
+                ),
+                BudgetStatus::ProjectedOver => (
+                    fl!("budget-projected-over", budget = budget.as_str()),
+                    UsageLevel::Warning,
+                ),
+                BudgetStatus::Over => (
+                    fl!("budget-over", budget = budget.as_str()),
+                    UsageLevel::Critical,
+                ),
+            };
+
+            let mut notice = widget::text::caption(notice);
+            if let Some(color) = usage_color(level, is_stale) {
+                notice = notice.class(cosmic::theme::Text::Color(color));
+            }
+
+            section = section.push(notice);
+        }
+
+        widget::container(section).into()
+    }
+
+    /// Sends a desktop notification when extra usage goes, or is on pace to go, over
+    /// the soft budget. Every alert is sent once per month.
+    fn check_budget(&mut self) -> Task<cosmic::Action<Message>> {
+        let Some(budget) = self.config.extra_usage_budget else {
+            return Task::none();
+        };
+
+        let Some(used_credits) = self
+            .usage
+            .as_ref()
+            .filter(|usage| usage.extra_usage.is_enabled)
+            .and_then(|usage| usage.extra_usage.used_credits)
+        else {
+            return Task::none();
+        };
+
+        let now = chrono::Local::now();
+        let projected = budget::projected_month_spend(used_credits, now);
+        let status = budget::budget_status(used_credits, projected, budget);
+        let month = (now.year(), now.month());
+
+        let body = match (status, projected) {
+            (BudgetStatus::Over, _) => fl!(
+                "budget-alert-over-body",
+                spent = budget::format_credits(used_credits),
+                budget = budget::format_credits(budget)
+            ),
+            (BudgetStatus::ProjectedOver, Some(projected)) => fl!(
+                "budget-alert-projected-over-body",
+                projected = budget::format_credits(projected),
+                budget = budget::format_credits(budget)
+            ),
+            _ => return Task::none(),
+        };
+
+        if self
+            .budget_alert
+            .is_some_and(|(alert_month, alert)| alert_month == month && alert >= status)
+        {
+            return Task::none();
+        }
+
+        log::info!("extra usage budget alert: {status:?}");
+        self.budget_alert = Some((month, status));
+
+        let summary = if status == BudgetStatus::Over {
+            fl!("budget-alert-over")
+        } else {
+            fl!("budget-alert-projected-over")
+        };
+
+        Task::future(async move {
+            if let Err(error) = notifications::send_notification(
+                <AppModel as cosmic::Application>::APP_ID,
+                &summary,
+                &body,
+            )
+            .await
+            {
+                log::warn!("budget alert not shown: {error}");
+            }
+        })
+        .discard()
+    }
+
+    /// Settings page of the popup. Changes are saved right away and applied live.
+    fn settings_view(&self) -> Element<'_, Message> {
+        let thresholds = self.config.default_thresholds;
+
+        let mut periods = widget::settings::section().title(fl!("settings-usage-periods"));
+        for name in PERIOD_NAMES.into_iter().chain(["extra_usage"]) {
+            let is_visible = !self
+                .config
+                .hidden_periods
+                .iter()
+                .any(|hidden| hidden == name);
+
+            periods = periods.add(widget::settings::item(
+                period_label(name),
+                widget::toggler(is_visible)
+                    .on_toggle(move |is_visible| Message::SetPeriodVisible(name, is_visible)),
+            ));
+        }
+
+        let polling = widget::settings::section()
+            .title(fl!("settings-polling"))
+            .add(widget::settings::item(
+                fl!("settings-poll-interval"),
+                widget::dropdown(
+                    &self.poll_interval_labels,
+                    POLL_INTERVALS
+                        .iter()
+                        .position(|poll_interval| *poll_interval == self.config.poll_interval),
+                    Message::SetPollInterval,
+                ),
+            ));
+
+        let panel = widget::settings::section()
+            .title(fl!("settings-panel"))
+            .add(widget::settings::item(
+                fl!("settings-panel-content"),
+                widget::dropdown(
+                    &self.panel_content_labels,
+                    PANEL_CONTENTS
+                        .iter()
+                        .position(|panel_content| *panel_content == self.config.panel_content),
+                    Message::SetPanelContent,
+                ),
+            ))
+            .add(widget::settings::item(
+                fl!("settings-panel-period"),
+                widget::dropdown(
+                    &self.period_labels,
+                    PERIOD_NAMES
+                        .iter()
+                        .position(|name| *name == self.config.panel_period),
+                    Message::SetPanelPeriod,
+                ),
+            ));
+
+        let alerts = widget::settings::section()
+            .title(fl!("settings-alerts"))
+            .add(widget::settings::item(
+                fl!("settings-warning-threshold"),
+                widget::dropdown(
+                    &THRESHOLD_LABELS,
+                    THRESHOLDS
+                        .iter()
+                        .position(|threshold| *threshold == thresholds.warning),
+                    Message::SetWarningThreshold,
+                ),
+            ))
+            .add(widget::settings::item(
+                fl!("settings-critical-threshold"),
+                widget::dropdown(
+                    &THRESHOLD_LABELS,
+                    THRESHOLDS
+                        .iter()
+                        .position(|threshold| *threshold == thresholds.critical),
+                    Message::SetCriticalThreshold,
+                ),
+            ))
+            .add(widget::settings::item(
+                fl!("settings-extra-usage-budget"),
+                widget::dropdown(
+                    &self.extra_usage_budget_labels,
+                    EXTRA_USAGE_BUDGETS
+                        .iter()
+                        .position(|budget| *budget == self.config.extra_usage_budget),
+                    Message::SetExtraUsageBudget,
+                ),
+            ));
+
+        let advanced = widget::settings::section()
+            .title(fl!("settings-advanced"))
+            .add(widget::settings::item(
+                fl!("settings-credential-backend"),
+                widget::dropdown(
+                    &self.credential_backend_labels,
+                    CREDENTIAL_BACKENDS
+                        .iter()
+                        .position(|backend| *backend == self.config.credential_backend),
+                    Message::SetCredentialBackend,
+                ),
+            ))
+            .add(widget::settings::item(
+                fl!("settings-log-level"),
+                widget::dropdown(
+                    &self.log_level_labels,
+                    LOG_LEVELS
+                        .iter()
+                        .position(|log_level| *log_level == self.config.log_level),
+                    Message::SetLogLevel,
+                ),
+            ));
+
+        widget::column()
+            .spacing(12)
+            .padding(8)
+            .push(
+                widget::row()
+                    .spacing(8)
+                    .align_y(Alignment::Center)
+                    .push(
+                        widget::button::icon(widget::icon::from_name("go-previous-symbolic"))
+                            .on_press(Message::CloseSettings),
+                    )
+                    .push(widget::text::heading(fl!("settings"))),
+            )
+            .push(polling)
+            .push(periods)
+            .push(panel)
+            .push(alerts)
+            .push(advanced)
+            .into()
+    }
+
+    /// Wraps the panel button in the tooltip shown while hovering it. The panel hides
+    /// it when the pointer leaves and while the popup is open.
+    fn hover_tooltip<'a>(
+        &'a self,
+        button: impl Into<Element<'a, Message>>,
+    ) -> Element<'a, Message> {
+        self.core
+            .applet
+            .applet_tooltip(
+                button,
+                self.hover_summary(),
+                self.popup.is_some(),
+                Message::Surface,
+                None,
+            )
+            .into()
+    }
+
+    /// Key numbers of the usage for the hover tooltip: the 5-hour and weekly
+    /// utilization and the next reset.
+    fn hover_summary(&self) -> String {
+        if !self.is_usage_visible {
+            return fl!("hover-not-logged-in");
+        }
+
+        let Some(usage) = &self.usage else {
+            return fl!("hover-no-usage-data");
+        };
+
+        let mut lines = vec![
+            fl!(
+                "hover-period-usage",
+                period = period_label("five_hour"),
+                utilization = format!("{:.0}", usage.five_hour.utilization)
+            ),
+            fl!(
+                "hover-period-usage",
+                period = period_label("seven_day"),
+                utilization = format!("{:.0}", usage.seven_day.utilization)
+            ),
+        ];
+
+        let next_reset = usage
+            .periods()
+            .into_iter()
+            .filter_map(|(name, period)| period.resets_at.map(|resets_at| (name, resets_at)))
+            .filter(|(_, resets_at)| *resets_at > self.now)
+            .min_by_key(|(_, resets_at)| *resets_at);
+
+        if let Some((name, resets_at)) = next_reset {
+            lines.push(fl!(
+                "hover-next-reset",
+                reset = reset_countdown(resets_at, self.now),
+                period = period_label(name)
+            ));
+        }
+
+        if self.is_usage_stale()
+            && let Some(updated_at) = self.usage_updated_at
+        {
+            lines.push(time_since_update(updated_at, self.now));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Dashboard of the details window: account, every usage period with its history,
+    /// and diagnostics.
+    fn details_view(&self) -> Element<'_, Message> {
+        let mut dashboard = widget::column().spacing(24).padding(24).push(
+            widget::row()
+                .spacing(8)
+                .align_y(Alignment::Center)
+                .push(widget::text::title3(fl!("details-title")).width(Length::Fill))
+                .push(
+                    widget::button::icon(widget::icon::from_name("view-refresh-symbolic"))
+                        .on_press(Message::RefreshUsage),
+                ),
+        );
+
+        let Some(credentials) = self.credentials.active() else {
+            return details_container(dashboard.push(widget::text(fl!("details-not-logged-in"))));
+        };
+
+        let token_expiry = credentials.expires_at.map_or_else(
+            || fl!("details-token-expiry-unknown"),
+            |expires_at| {
+                expires_at
+                    .with_timezone(&chrono::Local)
+                    .format("%a %H:%M")
+                    .to_string()
+            },
+        );
+
+        dashboard = dashboard.push(
+            widget::settings::section()
+                .title(fl!("details-account"))
+                .add(widget::settings::item(
+                    fl!("details-profile"),
+                    widget::text(credentials.profile_label()),
+                ))
+                .add(widget::settings::item(
+                    fl!("details-token-expiry"),
+                    widget::text(token_expiry),
+                ))
+                .add(widget::settings::item(
+                    fl!("details-other-accounts"),
+                    widget::text(self.profile_ids.len().saturating_sub(1).to_string()),
+                )),
+        );
+
+        if let Some(usage) = &self.usage {
+            let is_stale = self.is_usage_stale();
+
+            let mut periods = widget::settings::section().title(fl!("details-usage"));
+            let mut history = widget::settings::section().title(fl!("details-history"));
+
+            for (name, period) in usage.periods() {
+                let thresholds = self.config.thresholds(name);
+
+                periods = periods.add(usage_section(
+                    period_label(name),
+                    period.utilization,
+                    thresholds.level(period.utilization),
+                    is_stale,
+                    period
+                        .resets_at
+                        .map(|resets_at| reset_countdown(resets_at, self.now)),
+                ));
+
+                history = history.add(
+                    widget::column()
+                        .spacing(4)
+                        .padding(2)
+                        .push(widget::text(period_label(name)))
+                        .push(usage_chart(
+                            self.usage_history.peaks(name, self.now, CHART_SLOTS),
+                            thresholds,
+                        ))
+                        .push(
+                            widget::row()
+                                .push(
+                                    widget::text::caption(fl!("details-history-start"))
+                                        .width(Length::Fill),
+                                )
+                                .push(widget::text::caption(fl!("details-history-end"))),
+                        ),
+                );
+            }
+
+            if usage.extra_usage.is_enabled {
+                periods = periods.add(self.extra_usage_section(&usage.extra_usage, is_stale));
+            }
+
+            if let Some(updated_at) = self.usage_updated_at {
+                dashboard = dashboard.push(widget::text::caption(time_since_update(
+                    updated_at, self.now,
+                )));
+            }
+
+            dashboard = dashboard.push(periods).push(history);
+        } else {
+            dashboard = dashboard.push(widget::text::caption(fl!("no-usage-data")));
+        }
+
+        let mut diagnostics = widget::settings::section()
+            .title(fl!("diagnostics"))
+            .add(widget::text::caption(self.polling_policy()))
+            .add(widget::text::caption(self.monitor_status()));
+
+        if self.usage_failures > 0 {
+            diagnostics = diagnostics.add(widget::text::caption(self.stale_usage_notice()));
+        }
+
+        if let Some(error) = &self.credentials_save_error {
+            diagnostics = diagnostics.add(widget::text::caption(fl!(
+                "diagnostics-credentials-not-saved",
+                details = error.details.as_str()
+            )));
+        }
+
+        details_container(dashboard.push(diagnostics))
+    }
+
+    /// Saves a setting through `update`, which also applies it to the config in memory.
+    /// The config watcher then applies it to the monitor, here and in other instances.
+    fn update_config(
+        &mut self,
+        update: impl FnOnce(&mut Config, &cosmic_config::Config) -> Result<bool, cosmic_config::Error>,
+    ) {
+        let Some(config_handler) = &self.config_handler else {
+            log::warn!("setting not saved, config unavailable");
+            return;
+        };
+
+        if let Err(why) = update(&mut self.config, config_handler) {
+            log::error!("error saving config: {why}");
+        }
+    }
+
+    /// Whether the usage data is older than the configured staleness limit.
+    fn is_usage_stale(&self) -> bool {
+        self.usage_updated_at.is_some_and(|updated_at| {
+            self.now.signed_duration_since(updated_at).num_seconds()
+                >= i64::try_from(self.config.stale_after).unwrap_or(i64::MAX)
+        })
+    }
+
+    /// Describes the active polling policy and the conditions that selected it.
+    fn polling_policy(&self) -> String {
+        if !self.is_leader {
+            return fl!("polling-by-other-instance");
+        }
+
+        let poll_interval = self.config.poll_interval(self.power_state).as_secs();
+        let power_source = if self.power_state.is_on_battery {
+            fl!("polling-on-battery")
+        } else {
+            fl!("polling-on-ac-power")
+        };
+        let network = if self.power_state.is_metered {
+            fl!("polling-metered-network")
+        } else {
+            fl!("polling-unmetered-network")
+        };
+
+        let every = if poll_interval.is_multiple_of(60) {
+            fl!("minutes", minutes = poll_interval / 60)
+        } else {
+            fl!("seconds", seconds = poll_interval)
+        };
+
+        fl!(
+            "polling-every",
+            every = every,
+            power_source = power_source,
+            network = network
+        )
+    }
+
+    /// Describes what the usage monitor is doing and when it last succeeded.
+    fn monitor_status(&self) -> String {
+        let current = if !self.is_leader {
+            fl!("monitor-other-instance")
+        } else if let Some(reason) = self.monitor_pause {
+            match reason {
+                PauseReason::Suspended => fl!("monitor-paused-suspended"),
+            }
+        } else if self.is_fetching_usage {
+            fl!("monitor-updating")
+        } else if let Some(next_fetch_at) = self.next_fetch_at {
+            fl!(
+                "monitor-next-update",
+                time = next_fetch_at
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M")
+                    .to_string()
+            )
+        } else {
+            fl!("monitor-waiting")
+        };
+
+        match (self.usage_updated_at, self.usage_latency) {
+            (Some(updated_at), Some(latency)) => fl!(
+                "monitor-last-update",
+                current = current,
+                time = updated_at
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M:%S")
+                    .to_string(),
+                latency = latency.as_millis().to_string()
+            ),
+            _ => current,
+        }
+    }
+
+    /// Explains why the usage shown in the popup may be out of date.
+    fn stale_usage_notice(&self) -> String {
+        let reason = if self.is_offline {
+            fl!("stale-offline")
+        } else {
+            fl!("stale-update-failed")
+        };
+
+        match self.usage_updated_at {
+            Some(updated_at) => fl!(
+                "stale-showing-usage-from",
+                reason = reason,
+                time = updated_at
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M")
+                    .to_string()
+            ),
+            None => fl!("stale-no-usage-data", reason = reason),
+        }
+    }
+
+    /// Shows usage data fetched by this instance or read from the usage cache.
+    fn apply_usage(
+        &mut self,
+        usage: &claude::ClaudeUsageResponse,
+        fetched_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        self.usage = Some(usage.clone());
+        self.usage_history.record(usage, fetched_at);
+        self.usage_updated_at = Some(fetched_at);
+
+        // Don't keep showing fresh data as stale until the next tick.
+        self.now = chrono::Utc::now();
+    }
+
+    /// Shows the usage published by the polling instance if it belongs to the active
+    /// profile and is newer than the usage shown.
+    fn load_usage_cache(&mut self) {
+        let cache = match usage_cache::get_usage_cache() {
+            Ok(cache) => cache,
+            Err(error) => {
+                log::debug!("usage cache not loaded: {error}");
+                return;
+            }
+        };
+
+        if self.credentials.active_profile.as_ref() != Some(&cache.profile_id) {
+            log::debug!("usage cache belongs to another profile");
+            return;
+        }
+
+        if self
+            .usage_updated_at
+            .is_some_and(|updated_at| updated_at >= cache.fetched_at)
+        {
+            return;
+        }
+
+        log::debug!(
+            "showing usage from the cache fetched at {}",
+            cache.fetched_at
+        );
+        self.apply_usage(&cache.usage, cache.fetched_at);
+    }
+
+    /// Fetches usage data right away, through the monitor of this instance or by asking
+    /// the instance that polls.
+    fn request_fetch(&mut self) {
+        if self.is_leader {
+            self.send_to_monitor(MonitorCommand::FetchNow);
+        } else if let Err(error) = leader::request_fetch() {
+            log::error!("usage fetch not requested: {error}");
+        }
+    }
+
+    /// Sends a command to the running usage monitor, if any.
+    fn send_to_monitor(&mut self, command: MonitorCommand) {
+        let Some(monitor) = &self.monitor else {
+            return;
+        };
+
+        if monitor.send(command).is_err() {
+            log::debug!("usage monitor stopped, dropping its command channel");
+            self.monitor = None;
+        }
+    }
+
+    /// Applies a credential change in memory and saves it. While saving fails, the
+    /// error is shown in the popup and saving is retried in the background with an
+    /// increasing delay.
+    fn change_credentials(
+        &mut self,
+        change: credentials::CredentialsChange,
+    ) -> Task<cosmic::Action<Message>> {
+        let mut store = self.credentials.clone();
+        change.apply(&mut store);
+        self.set_credentials(store);
+
+        self.pending_credentials_changes.push(change);
+        self.save_credentials()
+    }
+
+    /// Applies the credential changes that aren't saved yet to a copy of the store.
+    fn with_pending_changes(
+        &self,
+        mut store: credentials::CredentialStore,
+    ) -> credentials::CredentialStore {
+        for change in &self.pending_credentials_changes {
+            change.apply(&mut store);
+        }
+
+        store
+    }
+
+    /// Saves the pending credential changes in the background, one save at a time.
+    fn save_credentials(&mut self) -> Task<cosmic::Action<Message>> {
+        if self.is_saving_credentials || self.pending_credentials_changes.is_empty() {
+            return Task::none();
+        }
+
+        self.is_saving_credentials = true;
+
+        let changes = self.pending_credentials_changes.clone();
+        let saved = changes.len();
+
+        Task::perform(
+            credentials::save_credentials_changes(
+                self.config.credential_backend,
+                changes,
+                self.credentials.clone(),
+            ),
+            move |result| cosmic::Action::App(Message::CredentialsSaved(saved, result)),
+        )
+    }
+
+    /// Records a failed save and schedules a retry with exponential backoff.
+    fn credentials_save_failed(&mut self, error: String) -> Task<cosmic::Action<Message>> {
+        log::error!("credentials not saved: {error}");
+        self.credentials_save_error = Some(AppError::new(ErrorKind::CredentialsSave, error));
+        self.credentials_save_attempts = self.credentials_save_attempts.saturating_add(1);
+
+        if self.is_credentials_save_scheduled {
+            return Task::none();
+        }
+
+        let delay = CREDENTIALS_SAVE_RETRY_DELAY
+            .saturating_mul(2_u32.saturating_pow(self.credentials_save_attempts - 1))
+            .min(CREDENTIALS_SAVE_MAX_RETRY_DELAY);
+
+        log::info!("retrying to save credentials in {}s", delay.as_secs());
+        self.is_credentials_save_scheduled = true;
+
+        Task::perform(tokio::time::sleep(delay), |()| {
+            cosmic::Action::App(Message::SaveCredentialsRetry)
+        })
+    }
+
+    /// Replaces the credential store. When the active profile changes, here or in
+    /// another process, the usage of the previous account is dropped so it is never
+    /// shown for the new one.
+    fn set_credentials(&mut self, store: credentials::CredentialStore) {
+        let is_profile_changed = self.credentials.active_profile != store.active_profile;
+
+        self.credentials = store;
+        self.sync_profiles();
+
+        if is_profile_changed {
+            self.usage = None;
+            self.usage_history.clear();
+            self.usage_updated_at = None;
+
+            // Show what the polling instance published for the new profile, if anything.
+            self.load_usage_cache();
+        }
+    }
+
+    /// Propagates changes of the credential store to the profile switcher and to the
+    /// token read by the usage monitor.
+    fn sync_profiles(&mut self) {
+        self.monitor_token.set(
+            self.credentials
+                .active()
+                .map(|credentials| credentials.access_token.clone())
+                .unwrap_or_default(),
+        );
+
+        (self.profile_ids, self.profile_labels) = self
+            .credentials
+            .profiles
+            .iter()
+            .map(|(profile_id, credentials)| (profile_id.clone(), credentials.profile_label()))
+            .unzip();
+    }
+
+    /// Position of the active profile in the profile switcher.
+    fn active_profile_index(&self) -> Option<usize> {
+        let active_profile = self.credentials.active_profile.as_ref()?;
+
+        self.profile_ids
+            .iter()
+            .position(|profile_id| profile_id == active_profile)
+    }
+}
+
+/// Banner of an error with its suggested action and a button to copy its details.
+fn error_banner(error: &AppError) -> Element<'_, Message> {
+    let (action_label, action) = match error.suggested_action() {
+        SuggestedAction::Retry if error.kind == ErrorKind::CredentialsSave => {
+            (fl!("error-action-retry"), Message::SaveCredentialsClicked)
+        }
+        // Moving is retried by choosing the storage again.
+        SuggestedAction::Retry if error.kind == ErrorKind::CredentialsMove => {
+            (fl!("error-action-retry"), Message::OpenSettings)
+        }
+        SuggestedAction::Retry => (fl!("error-action-retry"), Message::RefreshUsage),
+        SuggestedAction::Login => (fl!("error-action-login"), Message::LoginClicked),
+    };
+
+    let mut banner = widget::column()
+        .spacing(4)
+        .padding(2)
+        .push(widget::text::heading(error.message()));
+
+    if let Some(request_id) = &error.request_id {
+        banner = banner.push(widget::text::caption(fl!(
+            "error-request-id",
+            id = request_id.as_str()
+        )));
+    }
+
+    let mut actions = widget::row()
+        .spacing(8)
+        .push(widget::button::suggested(action_label).on_press(action))
+        .push(
+            widget::button::text(fl!("error-copy-details"))
+                .on_press(Message::CopyErrorDetails(error.report())),
+        );
+
+    if error.is_dismissible() {
+        actions = actions
+            .push(widget::button::text(fl!("error-dismiss")).on_press(Message::DismissError));
+    }
+
+    widget::container(banner.push(actions)).into()
+}
+
+/// Progress bar of a usage period with its label, utilization in percent and, if the
+/// period resets, the time left until then.
+fn usage_section(
+    label: String,
+    utilization: f32,
+    level: UsageLevel,
+    is_stale: bool,
+    reset_countdown: Option<String>,
+) -> Element<'static, Message> {
+    let mut percentage = widget::text(format!("{utilization:.0}%"));
+
+    if let Some(color) = usage_color(UsageLevel::Normal, is_stale) {
+        percentage = percentage.class(cosmic::theme::Text::Color(color));
+    }
+
+    let mut section = widget::column()
+        .spacing(2)
+        .padding(2)
+        .push(widget::text(label))
+        .push(
+            widget::progress_bar(0.0..=1.0, utilization / 100.0)
+                .height(6.0)
+                .class(usage_bar_class(level, is_stale)),
+        )
+        .push(percentage);
+
+    if let Some(reset_countdown) = reset_countdown {
+        section = section.push(widget::text::caption(reset_countdown));
+    }
+
+    widget::container(section).into()
+}
+
+/// Scrollable background of the details window.
+fn details_container<'a>(dashboard: widget::Column<'a, Message>) -> Element<'a, Message> {
+    widget::container(widget::scrollable(dashboard))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .class(cosmic::theme::Container::Background)
+        .into()
+}
+
+/// Column chart of the highest utilization of a usage period in each slot of the
+/// history, oldest first. Slots without samples stay empty.
+fn usage_chart(peaks: Vec<Option<f32>>, thresholds: UsageThresholds) -> Element<'static, Message> {
+    let mut chart = widget::row()
+        .spacing(2)
+        .height(CHART_HEIGHT)
+        .align_y(Alignment::End);
+
+    for peak in peaks {
+        let Some(utilization) = peak else {
+            chart = chart.push(widget::Space::new(Length::Fill, 0.0));
+            continue;
+        };
+
+        // Keep a sliver visible at 0%, so slots with samples never look empty.
+        let height = (utilization.clamp(0.0, 100.0) / 100.0 * CHART_HEIGHT).max(1.0);
+        let color = usage_level_color(thresholds.level(utilization));
+
+        chart = chart.push(
+            widget::container(widget::Space::new(Length::Fill, height))
+                .width(Length::Fill)
+                .class(cosmic::theme::Container::custom(move |theme| {
+                    cosmic::iced::widget::container::Style {
+                        background: Some(
+                            color
+                                .unwrap_or_else(|| theme.cosmic().accent_color().into())
+                                .into(),
+                        ),
+                        ..Default::default()
+                    }
+                })),
+        );
+    }
+
+    chart.into()
+}
+
+/// Theme color of a usage period close to its limit, if it is close at all.
+fn usage_level_color(level: UsageLevel) -> Option<cosmic::iced::Color> {
+    let theme = cosmic::theme::active();
+
+    match level {
+        UsageLevel::Normal => None,
+        UsageLevel::Warning => Some(theme.cosmic().warning_color().into()),
+        UsageLevel::Critical => Some(theme.cosmic().destructive_color().into()),
+    }
+}
+
+/// Color of usage text and icons: the color of the usage level, dimmed if the data
+/// is out of date. `None` keeps the default color.
+fn usage_color(level: UsageLevel, is_stale: bool) -> Option<cosmic::iced::Color> {
+    let color = match usage_level_color(level) {
+        Some(color) => color,
+        None if is_stale => cosmic::theme::active().cosmic().on_bg_color().into(),
+        None => return None,
+    };
+
+    Some(if is_stale { dimmed(color) } else { color })
+}
+
+fn dimmed(color: cosmic::iced::Color) -> cosmic::iced::Color {
+    cosmic::iced::Color {
+        a: color.a * STALE_OPACITY,
+        ..color
+    }
+}
+
+/// Progress bar style that fills the bar with the color of the usage level, dimmed if
+/// the data is out of date.
+fn usage_bar_class(level: UsageLevel, is_stale: bool) -> cosmic::theme::ProgressBar {
+    let color = usage_level_color(level);
+
+    if color.is_none() && !is_stale {
+        return cosmic::theme::ProgressBar::Primary;
+    }
+
+    cosmic::theme::ProgressBar::custom(move |theme| {
+        let mut style = cosmic::iced::widget::progress_bar::Catalog::style(
+            theme,
+            &cosmic::theme::ProgressBar::Primary,
+        );
+
+        if let Some(color) = color {
+            style.bar = color.into();
+        }
+
+        if is_stale && let cosmic::iced::Background::Color(color) = style.bar {
+            style.bar = dimmed(color).into();
+        }
+
+        style
+    })
+}
+
+/// Age of the usage data in words, such as "Updated 3 min ago".
+fn time_since_update(
+    updated_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let minutes = now.signed_duration_since(updated_at).num_minutes();
+
+    match minutes {
+        ..1 => fl!("updated-just-now"),
+        1..60 => fl!("updated-minutes-ago", minutes = minutes),
+        60..1440 => fl!("updated-hours-ago", hours = minutes / 60),
+        _ => fl!("updated-days-ago", days = minutes / 1440),
+    }
+}
+
+/// Describes when a period resets: the time left if it is less than a day away,
+/// otherwise the local weekday and time.
+fn reset_countdown(
+    resets_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let remaining = resets_at.signed_duration_since(now);
+
+    if remaining <= chrono::Duration::zero() {
+        return fl!("resetting-now");
+    }
+
+    if remaining >= chrono::Duration::days(1) {
+        return fl!(
+            "resets-at",
+            time = resets_at
+                .with_timezone(&chrono::Local)
+                .format("%a %H:%M")
+                .to_string()
+        );
+    }
+
+    // Round up, so the countdown never reads zero before the reset.
+    let minutes = (remaining.num_seconds() + 59) / 60;
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => fl!("resets-in-minutes", minutes = minutes),
+        (hours, 0) => fl!("resets-in-hours", hours = hours),
+        (hours, minutes) => fl!("resets-in-hours-minutes", hours = hours, minutes = minutes),
+    }
+}
+
+/// Human readable name of a usage period of the API response. Periods added to the API
+/// later keep their API name until they get a label.
+fn period_label(name: &str) -> String {
+    match name {
+        "five_hour" => fl!("period-five-hour"),
+        "seven_day" => fl!("period-seven-day"),
+        "seven_day_opus" => fl!("period-seven-day-opus"),
+        "seven_day_sonnet" => fl!("period-seven-day-sonnet"),
+        "seven_day_oauth_apps" => fl!("period-seven-day-oauth-apps"),
+        "iguana_necktie" => fl!("period-iguana-necktie"),
+        "seven_day_iguana_necktie" => fl!("period-seven-day-iguana-necktie"),
+        "extra_usage" => fl!("extra-usage"),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    // Polling instance with a running monitor whose access token expires at
+    // `expires_at`, and the commands the monitor receives.
+    fn polling_instance(
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> (AppModel, UnboundedReceiver<MonitorCommand>) {
+        let (monitor, commands) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut credentials = credentials::CredentialStore::default();
+        credentials.insert(credentials::ClaudeCredentials {
+            access_token: "access".into(),
+            refresh_token: "refresh".into(),
+            expires_at: Some(expires_at),
+            ..Default::default()
+        });
+
+        let app = AppModel {
+            is_leader: true,
+            monitor: Some(monitor),
+            credentials,
+            ..Default::default()
+        };
+
+        (app, commands)
+    }
+
+    #[test]
+    fn pauses_monitor_before_suspend() {
+        let (mut app, mut commands) =
+            polling_instance(chrono::Utc::now() + chrono::Duration::hours(1));
+
+        let _ = app.update(Message::PrepareForSleep(true));
+
+        assert_eq!(
+            commands.try_recv(),
+            Ok(MonitorCommand::Pause(PauseReason::Suspended))
+        );
+    }
+
+    #[test]
+    fn resumes_monitor_after_suspend() {
+        let (mut app, mut commands) =
+            polling_instance(chrono::Utc::now() + chrono::Duration::hours(1));
+
+        let _ = app.update(Message::PrepareForSleep(false));
+
+        assert_eq!(commands.try_recv(), Ok(MonitorCommand::Resume));
+        assert!(!app.is_refreshing_token);
+    }
+
+    // The monitor is resumed once the refresh finishes, so it doesn't poll with the
+    // expired token in the meantime.
+    #[test]
+    fn refreshes_expired_token_after_suspend() {
+        let (mut app, mut commands) =
+            polling_instance(chrono::Utc::now() - chrono::Duration::minutes(1));
+
+        let _ = app.update(Message::PrepareForSleep(false));
+
+        assert!(app.is_refreshing_token);
+        assert!(commands.try_recv().is_err());
+
+        let store = app.credentials.clone();
+        let _ = app.update(Message::RefreshTokenCompleted(
+            credentials::RefreshedCredentials {
+                store,
+                change: None,
+                save_error: None,
+            },
+        ));
+
+        assert_eq!(commands.try_recv(), Ok(MonitorCommand::Resume));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::fl;
+use chrono::{DateTime, Datelike, Local, Months, NaiveDate};
+use icu_decimal::{DecimalFormatter, input::Decimal};
+use icu_locale_core::Locale;
+use std::sync::OnceLock;
+
+/// Part of the month, in seconds, that must have passed before the spend is projected.
+/// Earlier projections swing too much with every request.
+const MIN_PROJECTION_ELAPSED: u64 = 24 * 60 * 60;
+
+/// Locale whose separators are used to format amounts, set by `init`.
+static AMOUNT_LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Extra usage spending compared to the soft budget set by the user. Ordered by
+/// severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BudgetStatus {
+    WithinBudget,
+    /// The spend projected for the end of the month exceeds the budget.
+    ProjectedOver,
+    /// The credits spent this month already exceed the budget.
+    Over,
+}
+
+// Returns the start of the current month and of the next one, in local time.
+fn month_bounds(now: DateTime<Local>) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    let start = now.date_naive().with_day(1)?;
+    let end = start.checked_add_months(Months::new(1))?;
+
+    let local_midnight = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)?
+            .and_local_timezone(Local)
+            .earliest()
+    };
+
+    Some((local_midnight(start)?, local_midnight(end)?))
+}
+
+// Extrapolates the credits spent so far to the end of the month at the same pace.
+pub fn projected_month_spend(used_credits: u64, now: DateTime<Local>) -> Option<u64> {
+    let (start, end) = month_bounds(now)?;
+
+    let elapsed = u64::try_from((now - start).num_seconds()).ok()?;
+    let month = u64::try_from((end - start).num_seconds()).ok()?;
+
+    (elapsed >= MIN_PROJECTION_ELAPSED).then(|| used_credits.saturating_mul(month) / elapsed)
+}
+
+pub fn budget_status(used_credits: u64, projected: Option<u64>, budget: u64) -> BudgetStatus {
+    if used_credits >= budget {
+        BudgetStatus::Over
+    } else if projected.is_some_and(|projected| projected > budget) {
+        BudgetStatus::ProjectedOver
+    } else {
+        BudgetStatus::WithinBudget
+    }
+}
+
+// Formats amounts with the separators of the first requested language that can be
+// parsed.
+pub fn init(requested_languages: &[impl ToString]) {
+    if let Some(locale) = requested_languages
+        .iter()
+        .find_map(|language| Locale::try_from_str(&language.to_string()).ok())
+    {
+        let _ = AMOUNT_LOCALE.set(locale);
+    }
+}
+
+// Formats an amount of credits as US dollars, such as "$1,234.56". The API reports
+// `used_credits` and `monthly_limit` in cents without a currency, and Claude bills
+// extra usage in US dollars. The number uses the separators of the user's locale and
+// the translation places the currency symbol, like "1.234,56 $" in German.
+pub fn format_credits(cents: u64) -> String {
+    let mut amount = Decimal::from(cents);
+    amount.multiply_pow10(-2);
+
+    let locale = AMOUNT_LOCALE.get().cloned().unwrap_or(Locale::UNKNOWN);
+    let amount = match DecimalFormatter::try_new((&locale).into(), Default::default()) {
+        Ok(formatter) => formatter.format(&amount).to_string(),
+        Err(error) => {
+            log::warn!("amount not formatted for {locale}: {error}");
+            amount.to_string()
+        }
+    };
+
+    fl!("credits-amount", amount = amount)
+}
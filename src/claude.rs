@@ -1,12 +1,11 @@
 use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
 use log::{info, trace};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha2::{Digest, Sha256};
-use std::fs;
 use std::io::{Read, Write};
 use std::net::TcpListener;
-use std::path::PathBuf;
 
 use crate::utils::extract_param_from_url;
 
@@ -15,19 +14,15 @@ const CLAUDE_USAGE_URL: &str = "https://api.anthropic.com/api/oauth/usage";
 const ANTHROPIC_AUTH_URL: &str = "https://claude.ai/oauth/authorize";
 const ANTHROPIC_TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
 const ANTHROPIC_CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
-const ANTHROPIC_AUTH_SCOPE: &str = "user:profile user:inference user:sessions:claude_code";
+pub const ANTHROPIC_AUTH_SCOPE: &str = "user:profile user:inference user:sessions:claude_code";
 const OAUTH_REDIRECT_PORT: u16 = 54545;
+/// Longest a token refresh may take. Refreshes hold the credentials lock shared with
+/// other instances, so a stalled request must not hold it forever.
+const TOKEN_REFRESH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
 /// Constants for Claude API error handler
 pub const ANTHROPIC_ERROR_AUTH_EXPIRED: &str = "OAuth token has expired";
 
-// Wrapper for the OAuth credentials of Claude AI.
-#[derive(Debug, Deserialize, Serialize, Default, Clone)]
-pub struct ClaudeCredentials {
-    pub access_token: String,
-    pub refresh_token: String,
-}
-
 // Error details structure for Claude API error responses
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ErrorDetails {
@@ -58,10 +53,11 @@ pub struct ClaudeErrorResponse {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct UsagePeriod {
     pub utilization: f32,
-    pub resets_at: Option<String>,
+    pub resets_at: Option<DateTime<Utc>>,
 }
 
-// It is part of the response of the Claude API usage endpoint.
+// It is part of the response of the Claude API usage endpoint. The amounts are in
+// cents of US dollars.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ExtraUsage {
     pub is_enabled: bool,
@@ -85,6 +81,31 @@ pub struct ClaudeUsageResponse {
     pub extra_usage: ExtraUsage,
 }
 
+impl ClaudeUsageResponse {
+    // Returns every usage period present in the response, along with its field name.
+    pub fn periods(&self) -> Vec<(&'static str, &UsagePeriod)> {
+        let optional_periods = [
+            ("seven_day_oauth_apps", &self.seven_day_oauth_apps),
+            ("seven_day_opus", &self.seven_day_opus),
+            ("seven_day_sonnet", &self.seven_day_sonnet),
+            ("iguana_necktie", &self.iguana_necktie),
+            ("seven_day_iguana_necktie", &self.seven_day_iguana_necktie),
+        ];
+
+        [
+            ("five_hour", &self.five_hour),
+            ("seven_day", &self.seven_day),
+        ]
+        .into_iter()
+        .chain(
+            optional_periods
+                .into_iter()
+                .filter_map(|(name, period)| period.as_ref().map(|period| (name, period))),
+        )
+        .collect()
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Organization {
     pub uuid: String,
@@ -103,6 +124,9 @@ pub struct AnthropicTokenResponse {
     pub refresh_token: String,
     pub expires_in: u64,
     pub token_type: String,
+    // Space separated list of granted scopes. Omitted when it matches the requested one.
+    #[serde(default)]
+    pub scope: Option<String>,
     pub organization: Organization,
     pub account: Account,
 }
@@ -110,6 +134,8 @@ pub struct AnthropicTokenResponse {
 pub struct GetUsageError {
     pub message: String,
     pub antropic_error_response: Option<ClaudeErrorResponse>,
+    // The API couldn't be reached at all (no network, DNS failure, timeout...).
+    pub is_connection_error: bool,
 }
 
 // Generates a code verifier for OAuth2 authorization.
@@ -275,12 +301,14 @@ pub async fn get_usage(access_token: &str) -> Result<ClaudeUsageResponse, GetUsa
         .map_err(|e| GetUsageError {
             message: format!("error requesting usage: {e}"),
             antropic_error_response: None,
+            is_connection_error: e.is_connect() || e.is_timeout(),
         })?;
 
     let status = response.status();
     let response_text = response.text().await.map_err(|e| GetUsageError {
         message: format!("error reading response text: {e}"),
         antropic_error_response: None,
+        is_connection_error: e.is_timeout(),
     })?;
 
     info!("request response (status {status}): {response_text}");
@@ -301,75 +329,22 @@ pub async fn get_usage(access_token: &str) -> Result<ClaudeUsageResponse, GetUsa
                 error_response.request_id
             ),
             antropic_error_response,
+            is_connection_error: false,
         });
     }
 
     Err(GetUsageError {
         message: format!("unexpected api response format: {response_text}"),
         antropic_error_response: None,
+        is_connection_error: false,
     })
 }
 
-// Function to get the credentials of the account. By default, the
-// credentials are stored in a json file within the $HOME/.claude directory.
-pub fn get_local_credentials() -> Result<ClaudeCredentials, String> {
-    trace!("getting $HOME environment variable");
-
-    let env_home =
-        std::env::var("HOME").map_err(|e| format!("home environment variable not set: {e}"))?;
-
-    trace!("reading credentials file located in {env_home}/.config/claude-tray/credentials.json");
-
-    let credentials =
-        fs::read_to_string(format!("{env_home}/.config/claude-tray/credentials.json"))
-            .map_err(|e| format!("failed to read credentials file: {e}"))?;
-
-    let credentials: ClaudeCredentials = serde_json::from_str(&credentials)
-        .map_err(|e| format!("error getting credentials: {e}"))?;
-
-    info!("credentials found in {env_home}/.config/claude-tray/credentials.json");
-
-    Ok(credentials)
-}
-
-// Store the credentials in the file credentials.json
-pub fn save_credentials_locally(credentials: &AnthropicTokenResponse) -> Result<(), String> {
-    let env_home =
-        std::env::var("HOME").map_err(|e| format!("home environment variable not set: {e}"))?;
-
-    let config_dir = PathBuf::from(env_home).join(".config/claude-tray");
-
-    trace!("saving credentials to {}", config_dir.display());
-
-    if !config_dir.exists() {
-        info!("credentials file not exists. creating new file");
-
-        fs::create_dir_all(&config_dir)
-            .map_err(|e| format!("failed to create config directory: {e}"))?;
-    }
-
-    let credentials_json = ClaudeCredentials {
-        access_token: credentials.access_token.clone(),
-        refresh_token: credentials.refresh_token.clone(),
-    };
-
-    let json_fmt = serde_json::to_string_pretty(&credentials_json)
-        .map_err(|e| format!("failed to serialize credentials: {e}"))?;
-
-    let credentials_file = config_dir.join("credentials.json");
-
-    fs::write(&credentials_file, json_fmt)
-        .map_err(|e| format!("failed to write credentials file: {e}"))?;
-
-    info!("credentials saved successfully");
-
-    Ok(())
-}
-
 // Refresh credentials using the provided refresh token
 pub async fn refresh_credentials(refresh_token: String) -> Result<AnthropicTokenResponse, String> {
     let response = reqwest::Client::new()
         .post(ANTHROPIC_TOKEN_URL)
+        .timeout(TOKEN_REFRESH_TIMEOUT)
         .header("Content-Type", "application/json")
         .header("Accept", "application/json")
         .json(&serde_json::json!({
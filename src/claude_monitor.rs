@@ -1,39 +1,473 @@
-use crate::{app::Message, claude};
+use crate::app::Message;
+use crate::claude;
+use crate::poll_scheduler::{PollScheduler, WarningThresholds};
+use chrono::{DateTime, Utc};
 use cosmic::iced::futures::channel::mpsc::Sender;
 use futures_util::SinkExt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-pub async fn claude_usage_monitoring(token: String, channel: &mut Sender<Message>) {
+/// Commands sent by the application to a running monitor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonitorCommand {
+    /// Fetch usage data right away instead of waiting for the next scheduled poll.
+    /// Ignored while the monitor is paused.
+    FetchNow,
+    /// Stop polling until the monitor is resumed.
+    Pause(PauseReason),
+    /// Leave the paused state and fetch usage data right away.
+    Resume,
+    /// Use a new regular poll interval from now on.
+    SetPollInterval(Duration),
+    /// Poll faster from new utilization thresholds on.
+    SetWarningThresholds(WarningThresholds),
+}
+
+/// Why the application paused the monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// The system is suspended.
+    Suspended,
+}
+
+/// Status reported by a running monitor to the application.
+#[derive(Debug, Clone)]
+pub enum MonitorEvent {
+    /// The monitor started and accepts commands through the handle.
+    Ready(MonitorHandle),
+    /// A usage request was sent.
+    FetchStarted,
+    /// Usage data was received.
+    FetchSucceeded {
+        usage: Box<claude::ClaudeUsageResponse>,
+        /// When the response was received.
+        at: DateTime<Utc>,
+        /// How long the request took.
+        latency: Duration,
+        /// Delay until the next scheduled fetch.
+        next_fetch_in: Duration,
+    },
+    /// The usage request failed.
+    FetchFailed {
+        error: String,
+        /// Identifier of the failed request, if the API returned one.
+        request_id: Option<String>,
+        /// Whether the API couldn't be reached at all.
+        is_offline: bool,
+        /// Whether the access token expired and needs to be refreshed.
+        is_auth_expired: bool,
+        /// Number of requests that failed since the last successful one.
+        failures: u32,
+        /// Delay until the request is retried.
+        retry_in: Duration,
+    },
+    /// Polling stopped until the application resumes the monitor.
+    Paused(PauseReason),
+    /// Polling continues after a pause.
+    Resumed,
+}
+
+/// Handle through which the application controls a running monitor.
+pub type MonitorHandle = UnboundedSender<MonitorCommand>;
+
+/// Access token shared between the application and the running monitor. The monitor
+/// reads it before every request, so refreshed tokens are picked up without restarting it.
+#[derive(Debug, Clone, Default)]
+pub struct SharedToken(Arc<RwLock<String>>);
+
+impl SharedToken {
+    pub fn get(&self) -> String {
+        self.0.read().map(|token| token.clone()).unwrap_or_default()
+    }
+
+    pub fn set(&self, token: String) {
+        if let Ok(mut current) = self.0.write() {
+            *current = token;
+        }
+    }
+}
+
+pub async fn claude_usage_monitoring(
+    token: SharedToken,
+    poll_interval: Duration,
+    warning_thresholds: WarningThresholds,
+    channel: &mut Sender<Message>,
+) {
+    monitor_usage(
+        token,
+        PollScheduler::new(poll_interval, warning_thresholds),
+        channel,
+        |token| async move { claude::get_usage(&token).await },
+    )
+    .await;
+}
+
+// Monitoring loop, generic over the request so it can be exercised without the API.
+async fn monitor_usage<F, Fut>(
+    token: SharedToken,
+    mut scheduler: PollScheduler,
+    channel: &mut Sender<Message>,
+    fetch_usage: F,
+) where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<claude::ClaudeUsageResponse, claude::GetUsageError>>,
+{
     log::info!("usage monitoring subscription started");
 
+    let (handle, mut commands) = tokio::sync::mpsc::unbounded_channel();
+    send_event(channel, MonitorEvent::Ready(handle)).await;
+
+    let mut pause = None;
+
     loop {
-        log::debug!("fetching usage data from claude api");
-        match claude::get_usage(&token).await {
-            Ok(usage) => {
-                log::info!(
-                    "usage data received: daily={:.0}%, weekly={:.0}%",
-                    usage.five_hour.utilization,
-                    usage.seven_day.utilization
-                );
-                let _ = channel.send(Message::UpdateUsage(usage)).await;
+        // Commands queued while the previous fetch was running. Fetch requests are
+        // served by the fetch below, unless the monitor is paused.
+        while let Ok(command) = commands.try_recv() {
+            match command {
+                MonitorCommand::SetPollInterval(poll_interval) => {
+                    scheduler.set_poll_interval(poll_interval);
+                }
+                MonitorCommand::SetWarningThresholds(warning_thresholds) => {
+                    scheduler.set_warning_thresholds(warning_thresholds);
+                }
+                MonitorCommand::Pause(reason) => pause = Some(reason),
+                MonitorCommand::Resume => pause = None,
+                MonitorCommand::FetchNow => {}
+            }
+        }
+
+        if let Some(reason) = pause.take() {
+            send_event(channel, MonitorEvent::Paused(reason)).await;
+
+            if !wait_for_resume(reason, &mut scheduler, &mut commands).await {
+                return;
             }
-            Err(error) => {
-                if let Some(antropic_error_response) = error.antropic_error_response
-                    && antropic_error_response
-                        .error
-                        .message
-                        .contains(claude::ANTHROPIC_ERROR_AUTH_EXPIRED)
-                {
-                    let _ = channel.send(Message::RefreshToken).await;
+
+            send_event(channel, MonitorEvent::Resumed).await;
+        }
+
+        log::debug!("fetching usage data from claude api");
+        send_event(channel, MonitorEvent::FetchStarted).await;
+
+        let started_at = Instant::now();
+        let result = fetch_usage(token.get()).await;
+        let latency = started_at.elapsed();
+
+        let delay =
+            match result {
+                Ok(usage) => {
+                    log::info!(
+                        "usage data received in {}ms: five_hour={:.0}%, seven_day={:.0}%",
+                        latency.as_millis(),
+                        usage.five_hour.utilization,
+                        usage.seven_day.utilization
+                    );
+                    let at = Utc::now();
+                    let delay = scheduler.next_delay(&usage, at);
+
+                    send_event(
+                        channel,
+                        MonitorEvent::FetchSucceeded {
+                            usage: Box::new(usage),
+                            at,
+                            latency,
+                            next_fetch_in: delay,
+                        },
+                    )
+                    .await;
+                    delay
+                }
+                Err(error) => {
+                    let is_auth_expired = error.antropic_error_response.as_ref().is_some_and(
+                        |antropic_error_response| {
+                            antropic_error_response
+                                .error
+                                .message
+                                .contains(claude::ANTHROPIC_ERROR_AUTH_EXPIRED)
+                        },
+                    );
+
+                    let request_id = error
+                        .antropic_error_response
+                        .map(|antropic_error_response| antropic_error_response.request_id);
+
+                    let delay = scheduler.next_delay_after_failure(error.is_connection_error);
+                    let failures = scheduler.consecutive_failures();
+
+                    if error.is_connection_error {
+                        log::warn!("usage api unreachable, {failures} failure(s) in a row");
+                    }
+
+                    log::error!("failed to fetch usage data: {}", error.message);
+                    send_event(
+                        channel,
+                        MonitorEvent::FetchFailed {
+                            error: error.message,
+                            request_id,
+                            is_offline: error.is_connection_error,
+                            is_auth_expired,
+                            failures,
+                            retry_in: delay,
+                        },
+                    )
+                    .await;
+                    delay
                 }
+            };
 
-                let error = error.message;
+        log::debug!("waiting {}s before next usage check", delay.as_secs());
+        pause = wait_for_next_fetch(delay, &mut scheduler, &mut commands).await;
+    }
+}
 
-                log::error!("failed to fetch usage data: {error}");
-                let _ = channel.send(Message::ThrowError(error)).await;
+async fn send_event(channel: &mut Sender<Message>, event: MonitorEvent) {
+    let _ = channel.send(Message::Monitor(event)).await;
+}
+
+// Sleeps until the next scheduled fetch, or until the application asks for one.
+// Returns the reason if the application paused the monitor in the meantime.
+async fn wait_for_next_fetch(
+    delay: Duration,
+    scheduler: &mut PollScheduler,
+    commands: &mut UnboundedReceiver<MonitorCommand>,
+) -> Option<PauseReason> {
+    let sleep = tokio::time::sleep(delay);
+    tokio::pin!(sleep);
+
+    loop {
+        tokio::select! {
+            () = &mut sleep => return None,
+            Some(command) = commands.recv() => match command {
+                MonitorCommand::FetchNow => {
+                    log::debug!("usage fetch requested by the application");
+                    return None;
+                }
+                MonitorCommand::Pause(reason) => return Some(reason),
+                // Resuming only matters while paused.
+                MonitorCommand::Resume => {}
+                // Applied from the next fetch on.
+                MonitorCommand::SetWarningThresholds(warning_thresholds) => {
+                    scheduler.set_warning_thresholds(warning_thresholds);
+                }
+                MonitorCommand::SetPollInterval(poll_interval) => {
+                    log::debug!("poll interval changed to {}s", poll_interval.as_secs());
+                    scheduler.set_poll_interval(poll_interval);
+
+                    // Don't keep waiting for longer than the new interval.
+                    if let Some(deadline) = tokio::time::Instant::now().checked_add(poll_interval)
+                        && deadline < sleep.deadline()
+                    {
+                        sleep.as_mut().reset(deadline);
+                    }
+                }
+            },
+        }
+    }
+}
+
+// Waits while the monitor is paused. Fetch requests are ignored, so a token that
+// expired during suspend isn't used before it is refreshed. Returns false if the
+// application dropped the command channel, which means the monitor is no longer needed.
+async fn wait_for_resume(
+    reason: PauseReason,
+    scheduler: &mut PollScheduler,
+    commands: &mut UnboundedReceiver<MonitorCommand>,
+) -> bool {
+    log::info!("usage monitoring paused: {reason:?}");
+
+    loop {
+        match commands.recv().await {
+            Some(MonitorCommand::Pause(_) | MonitorCommand::FetchNow) => {}
+            Some(MonitorCommand::SetPollInterval(poll_interval)) => {
+                scheduler.set_poll_interval(poll_interval);
+            }
+            Some(MonitorCommand::SetWarningThresholds(warning_thresholds)) => {
+                scheduler.set_warning_thresholds(warning_thresholds);
+            }
+            Some(MonitorCommand::Resume) => {
+                log::info!("usage monitoring resumed");
+                return true;
             }
+            None => return false,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use futures_util::StreamExt;
+    use std::sync::Mutex;
+
+    fn usage_response() -> claude::ClaudeUsageResponse {
+        serde_json::from_value(serde_json::json!({
+            "five_hour": { "utilization": 10.0, "resets_at": null },
+            "seven_day": { "utilization": 20.0, "resets_at": null },
+            "seven_day_oauth_apps": null,
+            "seven_day_opus": null,
+            "seven_day_sonnet": null,
+            "iguana_necktie": null,
+            "seven_day_iguana_necktie": null,
+            "extra_usage": {
+                "is_enabled": false,
+                "monthly_limit": null,
+                "used_credits": null,
+                "utilization": null
+            }
+        }))
+        .unwrap()
+    }
+
+    fn expired_token_error() -> claude::GetUsageError {
+        let antropic_error_response: claude::ClaudeErrorResponse =
+            serde_json::from_value(serde_json::json!({
+                "type": "error",
+                "error": {
+                    "type": "authentication_error",
+                    "message": claude::ANTHROPIC_ERROR_AUTH_EXPIRED,
+                    "details": { "error_visibility": "user_facing" }
+                },
+                "request_id": "req_test"
+            }))
+            .unwrap();
+
+        claude::GetUsageError {
+            message: "api error".into(),
+            antropic_error_response: Some(antropic_error_response),
+            is_connection_error: false,
+        }
+    }
+
+    // A running monitor must use the refreshed token instead of the one it started with.
+    #[tokio::test]
+    async fn monitor_picks_up_refreshed_token() {
+        let token = SharedToken::default();
+        token.set("expired-token".into());
+
+        let used_tokens = Arc::new(Mutex::new(Vec::new()));
+        let (mut sender, mut receiver) = cosmic::iced::futures::channel::mpsc::channel(10);
+
+        let monitor_token = token.clone();
+        let fetch_tokens = used_tokens.clone();
+        tokio::spawn(async move {
+            monitor_usage(
+                monitor_token,
+                PollScheduler::new(
+                    Duration::from_secs(300),
+                    WarningThresholds::from(&Config::default()),
+                ),
+                &mut sender,
+                move |token| {
+                    fetch_tokens.lock().unwrap().push(token.clone());
+                    async move {
+                        if token == "expired-token" {
+                            Err(expired_token_error())
+                        } else {
+                            Ok(usage_response())
+                        }
+                    }
+                },
+            )
+            .await;
+        });
+
+        let Some(Message::Monitor(MonitorEvent::Ready(handle))) = receiver.next().await else {
+            panic!("monitor didn't send its command channel");
+        };
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchStarted))
+        ));
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchFailed {
+                is_auth_expired: true,
+                failures: 1,
+                ..
+            }))
+        ));
+
+        token.set("refreshed-token".into());
+        handle.send(MonitorCommand::FetchNow).unwrap();
+
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchStarted))
+        ));
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchSucceeded { .. }))
+        ));
+        assert_eq!(
+            *used_tokens.lock().unwrap(),
+            ["expired-token", "refreshed-token"]
+        );
+    }
+
+    // A paused monitor must not poll until it is resumed, and must fetch right away then.
+    #[tokio::test]
+    async fn monitor_pauses_until_resumed() {
+        let (mut sender, mut receiver) = cosmic::iced::futures::channel::mpsc::channel(10);
+
+        tokio::spawn(async move {
+            monitor_usage(
+                SharedToken::default(),
+                PollScheduler::new(
+                    Duration::from_secs(300),
+                    WarningThresholds::from(&Config::default()),
+                ),
+                &mut sender,
+                |_| async { Ok(usage_response()) },
+            )
+            .await;
+        });
+
+        let Some(Message::Monitor(MonitorEvent::Ready(handle))) = receiver.next().await else {
+            panic!("monitor didn't send its command channel");
+        };
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchStarted))
+        ));
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchSucceeded { .. }))
+        ));
+
+        handle
+            .send(MonitorCommand::Pause(PauseReason::Suspended))
+            .unwrap();
+
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::Paused(
+                PauseReason::Suspended
+            )))
+        ));
+
+        // Fetch requests don't count while paused, only resuming does.
+        handle.send(MonitorCommand::FetchNow).unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), receiver.next())
+                .await
+                .is_err()
+        );
+
+        handle.send(MonitorCommand::Resume).unwrap();
 
-        log::debug!("waiting 5 minutes before next usage check");
-        tokio::time::sleep(std::time::Duration::from_secs(300)).await;
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::Resumed))
+        ));
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchStarted))
+        ));
+        assert!(matches!(
+            receiver.next().await,
+            Some(Message::Monitor(MonitorEvent::FetchSucceeded { .. }))
+        ));
     }
 }
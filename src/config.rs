@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::power::PowerState;
+use cosmic::cosmic_config::{self, CosmicConfigEntry, cosmic_config_derive::CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Shortest poll interval accepted, in seconds, so a typo can't flood the usage endpoint.
+pub const MIN_POLL_INTERVAL: u64 = 30;
+/// Longest poll interval accepted, in seconds, so the adaptive delays based on it stay
+/// in range.
+pub const MAX_POLL_INTERVAL: u64 = 24 * 60 * 60;
+
+/// What the applet shows in the panel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PanelContent {
+    /// The applet icon only.
+    #[default]
+    Icon,
+    /// Utilization of the chosen period, as a percentage.
+    Percentage,
+    /// The applet icon followed by the utilization percentage.
+    IconAndPercentage,
+    /// A small progress bar filled up to the utilization.
+    Bar,
+}
+
+/// Utilization, in percent, from which a usage period is shown as close to its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageThresholds {
+    pub warning: u32,
+    pub critical: u32,
+}
+
+impl Default for UsageThresholds {
+    fn default() -> Self {
+        Self {
+            warning: 70,
+            critical: 90,
+        }
+    }
+}
+
+/// How close a usage period is to its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageLevel {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl UsageThresholds {
+    pub fn level(&self, utilization: f32) -> UsageLevel {
+        if utilization >= self.critical as f32 {
+            UsageLevel::Critical
+        } else if utilization >= self.warning as f32 {
+            UsageLevel::Warning
+        } else {
+            UsageLevel::Normal
+        }
+    }
+}
+
+/// Where the credentials are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CredentialBackend {
+    /// A JSON file in the config directory of the applet.
+    #[default]
+    File,
+    /// The Secret Service of the desktop, such as GNOME Keyring or KWallet.
+    SecretService,
+}
+
+/// Most verbose messages written to the log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    #[default]
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// Configuration of the applet, persisted through cosmic-config and reloaded live when
+/// it changes.
+#[derive(Debug, Clone, CosmicConfigEntry, Eq, PartialEq)]
+#[version = 1]
+pub struct Config {
+    /// Seconds between two requests to the usage endpoint.
+    pub poll_interval: u64,
+    /// Seconds between two requests while the system runs on battery.
+    pub battery_poll_interval: u64,
+    /// Seconds between two requests while the network connection is metered.
+    pub metered_poll_interval: u64,
+    /// Age, in seconds, from which usage data is fetched again when the popup opens.
+    pub refresh_on_open_after: u64,
+    /// Age, in seconds, from which usage data is dimmed as out of date.
+    pub stale_after: u64,
+    /// What the applet shows in the panel.
+    pub panel_content: PanelContent,
+    /// Usage period shown in the panel, named like the field of the API response.
+    pub panel_period: String,
+    /// Usage periods hidden from the popup, named like the fields of the API response.
+    pub hidden_periods: Vec<String>,
+    /// Thresholds of the usage periods without their own entry.
+    pub default_thresholds: UsageThresholds,
+    /// Thresholds of single usage periods, keyed like the fields of the API response.
+    pub period_thresholds: BTreeMap<String, UsageThresholds>,
+    /// Soft monthly budget for extra usage, in cents like the amounts reported by the
+    /// API. Crossing it, or being on pace to, raises an alert.
+    pub extra_usage_budget: Option<u64>,
+    /// Where the credentials are stored.
+    pub credential_backend: CredentialBackend,
+    /// Most verbose messages written to the log.
+    pub log_level: LogLevel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: 300,
+            battery_poll_interval: 900,
+            metered_poll_interval: 900,
+            refresh_on_open_after: 60,
+            stale_after: 3600,
+            panel_content: PanelContent::default(),
+            panel_period: "five_hour".to_string(),
+            hidden_periods: Vec::new(),
+            default_thresholds: UsageThresholds::default(),
+            period_thresholds: BTreeMap::new(),
+            extra_usage_budget: None,
+            credential_backend: CredentialBackend::default(),
+            log_level: LogLevel::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Poll interval for the given power state, with the lower bound applied. When
+    /// several conditions apply, the longest of their intervals is used.
+    pub fn poll_interval(&self, power_state: PowerState) -> Duration {
+        let mut poll_interval = self.poll_interval;
+
+        if power_state.is_on_battery {
+            poll_interval = poll_interval.max(self.battery_poll_interval);
+        }
+
+        if power_state.is_metered {
+            poll_interval = poll_interval.max(self.metered_poll_interval);
+        }
+
+        Duration::from_secs(poll_interval.clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL))
+    }
+
+    /// Thresholds of a usage period, named like the field of the API response.
+    pub fn thresholds(&self, period: &str) -> UsageThresholds {
+        self.period_thresholds
+            .get(period)
+            .copied()
+            .unwrap_or(self.default_thresholds)
+    }
+}
@@ -0,0 +1,822 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::claude::{self, ANTHROPIC_AUTH_SCOPE, Account, AnthropicTokenResponse, Organization};
+use crate::config::CredentialBackend;
+use crate::fl;
+use crate::utils::write_atomically;
+
+/// Version of the schema written to the credentials file. Bump it every time the
+/// persisted format changes and add the matching step to `migrate_credentials`.
+pub const CREDENTIALS_SCHEMA_VERSION: u32 = 3;
+
+/// Files written before the schema was versioned don't carry a version field.
+const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// Profile key used for credentials that aren't linked to an account, like the ones
+/// migrated from files that only stored the token pair.
+const LEGACY_PROFILE_ID: &str = "default";
+
+/// Attributes that identify the credentials of the applet in the Secret Service.
+const SECRET_ATTRIBUTES: [(&str, &str); 1] = [("application", "com.github.jrdx0.ClaudeApplet")];
+
+/// Label of the credentials in the Secret Service, shown by keyring managers.
+const SECRET_LABEL: &str = "Claude Applet credentials";
+
+// Wrapper for the OAuth credentials of Claude AI.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct ClaudeCredentials {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    // Scopes granted by the authorization server.
+    pub scopes: Vec<String>,
+    // Moment in which the token was issued. Unknown for migrated credentials.
+    pub obtained_at: Option<DateTime<Utc>>,
+    // Moment in which the access token expires. Unknown for migrated credentials.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub account: Option<Account>,
+    pub organization: Option<Organization>,
+}
+
+impl ClaudeCredentials {
+    // Returns the key of the profile these credentials belong to.
+    pub fn profile_id(&self) -> String {
+        self.account
+            .as_ref()
+            .map_or_else(|| LEGACY_PROFILE_ID.into(), |account| account.uuid.clone())
+    }
+
+    // Returns a human readable name for the profile.
+    pub fn profile_label(&self) -> String {
+        match (&self.account, &self.organization) {
+            (Some(account), Some(organization)) => {
+                format!("{} ({})", account.email_address, organization.name)
+            }
+            (Some(account), None) => account.email_address.clone(),
+            _ => fl!("default-account"),
+        }
+    }
+
+    // Returns true when the access token is known to be expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+impl From<AnthropicTokenResponse> for ClaudeCredentials {
+    fn from(response: AnthropicTokenResponse) -> Self {
+        let obtained_at = Utc::now();
+        let expires_in = i64::try_from(response.expires_in).unwrap_or(i64::MAX);
+
+        // An omitted scope means the granted scope is the requested one (RFC 6749, 5.1).
+        let scope = response.scope.as_deref().unwrap_or(ANTHROPIC_AUTH_SCOPE);
+
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            token_type: response.token_type,
+            scopes: scope.split_whitespace().map(String::from).collect(),
+            obtained_at: Some(obtained_at),
+            expires_at: Duration::try_seconds(expires_in)
+                .and_then(|expires_in| obtained_at.checked_add_signed(expires_in)),
+            account: Some(response.account),
+            organization: Some(response.organization),
+        }
+    }
+}
+
+// Every Claude account the user has logged in with, keyed by `Account.uuid`.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct CredentialStore {
+    pub active_profile: Option<String>,
+    pub profiles: BTreeMap<String, ClaudeCredentials>,
+}
+
+impl CredentialStore {
+    // Returns the credentials of the active profile.
+    pub fn active(&self) -> Option<&ClaudeCredentials> {
+        self.active_profile
+            .as_ref()
+            .and_then(|profile_id| self.profiles.get(profile_id))
+    }
+
+    // Adds or replaces the profile of the given credentials and makes it the active one.
+    pub fn insert(&mut self, credentials: ClaudeCredentials) {
+        let profile_id = credentials.profile_id();
+
+        self.profiles.insert(profile_id.clone(), credentials);
+        self.active_profile = Some(profile_id);
+    }
+
+    // Replaces the credentials stored for `profile_id`. Credentials without an account
+    // are moved to their account key once a token response links them to one.
+    pub fn replace(&mut self, profile_id: &str, credentials: ClaudeCredentials) {
+        let new_profile_id = credentials.profile_id();
+
+        if new_profile_id != profile_id {
+            self.profiles.remove(profile_id);
+
+            if self.active_profile.as_deref() == Some(profile_id) {
+                self.active_profile = Some(new_profile_id.clone());
+            }
+        }
+
+        self.profiles.insert(new_profile_id, credentials);
+    }
+
+    // Makes the given profile the active one. Returns false if it doesn't exist.
+    pub fn set_active(&mut self, profile_id: &str) -> bool {
+        if !self.profiles.contains_key(profile_id) {
+            return false;
+        }
+
+        self.active_profile = Some(profile_id.into());
+        true
+    }
+}
+
+// A change made to the credentials in memory. Changes are saved by applying them to
+// the stored copy, so profiles changed by other processes in the meantime are kept.
+#[derive(Debug, Clone)]
+pub enum CredentialsChange {
+    // A login, which adds or replaces the profile and makes it the active one.
+    Login(ClaudeCredentials),
+    // Tokens of `profile_id` rotated by a refresh of this process.
+    Refresh {
+        profile_id: String,
+        credentials: ClaudeCredentials,
+    },
+    // Another profile was made the active one.
+    SetActive(String),
+}
+
+impl CredentialsChange {
+    pub fn apply(&self, store: &mut CredentialStore) {
+        match self {
+            CredentialsChange::Login(credentials) => store.insert(credentials.clone()),
+            CredentialsChange::Refresh {
+                profile_id,
+                credentials,
+            } => {
+                // Never replace tokens rotated after these with the revoked ones.
+                if store.profiles.get(profile_id).is_some_and(|stored| {
+                    stored.refresh_token != credentials.refresh_token
+                        && stored.obtained_at > credentials.obtained_at
+                }) {
+                    info!("keeping tokens of profile {profile_id} rotated by another process");
+                    return;
+                }
+
+                store.replace(profile_id, credentials.clone());
+            }
+            CredentialsChange::SetActive(profile_id) => {
+                if !store.set_active(profile_id) {
+                    warn!("profile {profile_id} not found, active profile not changed");
+                }
+            }
+        }
+    }
+}
+
+// Layout of the credentials file on disk.
+#[derive(Debug, Deserialize, Serialize)]
+struct CredentialsFile {
+    version: u32,
+    #[serde(flatten)]
+    store: CredentialStore,
+}
+
+// Returns the directory shared by all applet files, $HOME/.config/claude-tray.
+pub fn config_dir() -> Result<PathBuf, String> {
+    trace!("getting $HOME environment variable");
+
+    let env_home =
+        std::env::var("HOME").map_err(|e| format!("home environment variable not set: {e}"))?;
+
+    Ok(PathBuf::from(env_home).join(".config/claude-tray"))
+}
+
+// Returns the path of the credentials file, $HOME/.config/claude-tray/credentials.json.
+pub fn credentials_path() -> Result<PathBuf, String> {
+    Ok(config_dir()?.join("credentials.json"))
+}
+
+// Returns the path of the file rewritten every time the credentials are saved to the
+// Secret Service, $HOME/.config/claude-tray/credentials.changed.
+pub fn secret_changed_path() -> Result<PathBuf, String> {
+    Ok(config_dir()?.join("credentials.changed"))
+}
+
+// Returns the path of the lock file that serializes token refreshes between processes.
+fn lock_path(credentials_file: &Path) -> PathBuf {
+    credentials_file.with_extension("json.lock")
+}
+
+// Returns the directory shared by all applet files, creating it if it doesn't exist yet.
+pub fn ensure_config_dir() -> Result<PathBuf, String> {
+    let config_dir = config_dir()?;
+
+    if !config_dir.exists() {
+        info!("creating config directory {}", config_dir.display());
+
+        fs::create_dir_all(&config_dir)
+            .map_err(|e| format!("failed to create config directory: {e}"))?;
+    }
+
+    Ok(config_dir)
+}
+
+// Takes the exclusive lock over the credentials. It is held until the returned file
+// is dropped, and it is released by the kernel if the process dies while holding it.
+async fn lock_credentials() -> Result<File, String> {
+    ensure_config_dir()?;
+    let credentials_file = credentials_path()?;
+
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path(&credentials_file))
+        .map_err(|e| format!("failed to open credentials lock file: {e}"))?;
+
+    trace!("waiting for the credentials lock");
+
+    tokio::task::spawn_blocking(move || lock_file.lock().map(|()| lock_file))
+        .await
+        .map_err(|e| format!("failed to wait for the credentials lock: {e}"))?
+        .map_err(|e| format!("failed to lock credentials: {e}"))
+}
+
+// Upgrades a v1 file, which only stored the token pair, to the v2 layout.
+fn migrate_v1_to_v2(mut credentials: Map<String, Value>) -> Map<String, Value> {
+    credentials
+        .entry("token_type")
+        .or_insert_with(|| Value::from("Bearer"));
+    credentials
+        .entry("scopes")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    for field in ["obtained_at", "expires_at", "account", "organization"] {
+        credentials.entry(field).or_insert(Value::Null);
+    }
+
+    credentials
+}
+
+// Upgrades a v2 file, which stored a single account, to the v3 layout that keeps a
+// profile per account.
+fn migrate_v2_to_v3(credentials: Map<String, Value>) -> Map<String, Value> {
+    let profile_id = credentials
+        .get("account")
+        .and_then(|account| account.get("uuid"))
+        .and_then(Value::as_str)
+        .unwrap_or(LEGACY_PROFILE_ID)
+        .to_string();
+
+    let mut profile = credentials;
+    profile.remove("version");
+
+    let mut profiles = Map::new();
+    profiles.insert(profile_id.clone(), Value::Object(profile));
+
+    let mut store = Map::new();
+    store.insert("active_profile".into(), Value::from(profile_id));
+    store.insert("profiles".into(), Value::Object(profiles));
+
+    store
+}
+
+// Brings the raw content of a credentials file up to the current schema version.
+// Files written by a newer version of the applet are rejected instead of being
+// parsed partially, since fields this version doesn't know about would be lost on
+// the next save.
+fn migrate_credentials(raw: Value) -> Result<CredentialsFile, String> {
+    let Value::Object(mut credentials) = raw else {
+        return Err("credentials file is not a json object".into());
+    };
+
+    let mut version = match credentials.get("version") {
+        None => LEGACY_SCHEMA_VERSION,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or(format!("invalid credentials schema version: {version}"))?,
+    };
+
+    if version > CREDENTIALS_SCHEMA_VERSION {
+        return Err(format!(
+            "credentials file uses schema version {version}, but this version of the applet only supports up to version {CREDENTIALS_SCHEMA_VERSION}. update the applet or log in again"
+        ));
+    }
+
+    while version < CREDENTIALS_SCHEMA_VERSION {
+        info!(
+            "migrating credentials from schema version {version} to {}",
+            version + 1
+        );
+
+        credentials = match version {
+            1 => migrate_v1_to_v2(credentials),
+            2 => migrate_v2_to_v3(credentials),
+            _ => {
+                return Err(format!(
+                    "no migration for credentials schema version {version}"
+                ));
+            }
+        };
+
+        version += 1;
+    }
+
+    credentials.insert("version".into(), Value::from(version));
+
+    serde_json::from_value(Value::Object(credentials))
+        .map_err(|e| format!("error getting credentials: {e}"))
+}
+
+// Opens the default collection of the Secret Service, unlocking it if needed.
+async fn secret_service() -> Result<oo7::Keyring, String> {
+    let keyring = oo7::Keyring::new()
+        .await
+        .map_err(|e| format!("failed to open the secret service: {e}"))?;
+
+    keyring
+        .unlock()
+        .await
+        .map_err(|e| format!("failed to unlock the secret service: {e}"))?;
+
+    Ok(keyring)
+}
+
+// Reads the raw credentials stored in the backend. Returns None if nothing is stored
+// yet.
+async fn read_stored_credentials(backend: CredentialBackend) -> Result<Option<String>, String> {
+    match backend {
+        CredentialBackend::File => {
+            let credentials_file = credentials_path()?;
+
+            trace!(
+                "reading credentials file located in {}",
+                credentials_file.display()
+            );
+
+            match fs::read_to_string(&credentials_file) {
+                Ok(credentials) => Ok(Some(credentials)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("failed to read credentials file: {e}")),
+            }
+        }
+        CredentialBackend::SecretService => {
+            trace!("reading credentials from the secret service");
+
+            let items = secret_service()
+                .await?
+                .search_items(&SECRET_ATTRIBUTES.as_slice())
+                .await
+                .map_err(|e| format!("failed to search the secret service: {e}"))?;
+
+            let Some(item) = items.first() else {
+                return Ok(None);
+            };
+
+            let secret = item
+                .secret()
+                .await
+                .map_err(|e| format!("failed to read credentials from the secret service: {e}"))?;
+
+            String::from_utf8(secret.to_vec())
+                .map(Some)
+                .map_err(|e| format!("credentials in the secret service aren't text: {e}"))
+        }
+    }
+}
+
+// Replaces the credentials stored in the backend. Callers must hold the credentials
+// lock, since the whole store is replaced.
+async fn write_stored_credentials(
+    backend: CredentialBackend,
+    credentials: &str,
+) -> Result<(), String> {
+    ensure_config_dir()?;
+
+    match backend {
+        CredentialBackend::File => write_atomically(&credentials_path()?, credentials)
+            .map_err(|e| format!("failed to write credentials file: {e}")),
+        CredentialBackend::SecretService => {
+            secret_service()
+                .await?
+                .create_item(
+                    SECRET_LABEL,
+                    &SECRET_ATTRIBUTES.as_slice(),
+                    credentials,
+                    true,
+                )
+                .await
+                .map_err(|e| format!("failed to save credentials to the secret service: {e}"))?;
+
+            // The Secret Service doesn't tell other instances about the change, so
+            // they watch this file instead.
+            write_atomically(&secret_changed_path()?, &Utc::now().to_rfc3339())
+                .map_err(|e| format!("failed to write credentials change file: {e}"))
+        }
+    }
+}
+
+// Deletes the credentials stored in the backend.
+async fn delete_stored_credentials(backend: CredentialBackend) -> Result<(), String> {
+    match backend {
+        CredentialBackend::File => match fs::remove_file(credentials_path()?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(format!("failed to delete credentials file: {e}"))
+            }
+            _ => Ok(()),
+        },
+        CredentialBackend::SecretService => secret_service()
+            .await?
+            .delete(&SECRET_ATTRIBUTES.as_slice())
+            .await
+            .map_err(|e| format!("failed to delete credentials from the secret service: {e}")),
+    }
+}
+
+// Function to get the credentials of every account from the backend they are stored
+// in. Returns None if nobody logged in yet.
+pub async fn load_credentials(
+    backend: CredentialBackend,
+) -> Result<Option<CredentialStore>, String> {
+    let Some(credentials) = read_stored_credentials(backend).await? else {
+        return Ok(None);
+    };
+
+    let raw: Value = serde_json::from_str(&credentials)
+        .map_err(|e| format!("error getting credentials: {e}"))?;
+
+    let store = migrate_credentials(raw)?.store;
+
+    if store.active().is_some_and(ClaudeCredentials::is_expired) {
+        warn!("stored access token of the active profile is expired");
+    }
+
+    info!(
+        "credentials for {} profile(s) found in {backend:?}",
+        store.profiles.len()
+    );
+
+    Ok(Some(store))
+}
+
+// Stores the credentials in the backend. Callers must hold the credentials lock,
+// since the whole store is replaced.
+async fn save_stored_credentials(
+    backend: CredentialBackend,
+    store: &CredentialStore,
+) -> Result<(), String> {
+    trace!("saving credentials to {backend:?}");
+
+    let credentials_json = CredentialsFile {
+        version: CREDENTIALS_SCHEMA_VERSION,
+        store: store.clone(),
+    };
+
+    let json_fmt = serde_json::to_string_pretty(&credentials_json)
+        .map_err(|e| format!("failed to serialize credentials: {e}"))?;
+
+    write_stored_credentials(backend, &json_fmt).await?;
+
+    info!("credentials saved successfully");
+
+    Ok(())
+}
+
+// Returns the stored credentials that changes are applied to. Only missing
+// credentials fall back to the in-memory copy. Credentials that can't be read, or
+// that were written by a newer version of the applet, must not be overwritten.
+async fn stored_credentials_or(
+    backend: CredentialBackend,
+    in_memory: CredentialStore,
+) -> Result<CredentialStore, String> {
+    Ok(load_credentials(backend).await?.unwrap_or_else(|| {
+        warn!("no stored credentials yet, starting from in-memory credentials");
+        in_memory
+    }))
+}
+
+// Moves the credentials to another backend. The stored copy is moved, or the one in
+// memory if nothing is stored yet, and the old copy is deleted once the new one is
+// saved.
+pub async fn move_credentials(
+    from: CredentialBackend,
+    to: CredentialBackend,
+    in_memory: CredentialStore,
+) -> Result<(), String> {
+    let _lock = lock_credentials().await?;
+
+    let store = stored_credentials_or(from, in_memory).await?;
+    save_stored_credentials(to, &store).await?;
+
+    if let Err(error) = delete_stored_credentials(from).await {
+        warn!("credentials left behind in {from:?}: {error}");
+    }
+
+    info!("credentials moved from {from:?} to {to:?}");
+
+    Ok(())
+}
+
+// Saves changes made in memory. Under the credentials lock, the changes are applied
+// to the stored copy, which is then written back and returned, so tokens rotated by
+// other processes are never overwritten with revoked ones.
+pub async fn save_credentials_changes(
+    backend: CredentialBackend,
+    changes: Vec<CredentialsChange>,
+    in_memory: CredentialStore,
+) -> Result<CredentialStore, String> {
+    let _lock = lock_credentials().await?;
+
+    let mut store = stored_credentials_or(backend, in_memory).await?;
+
+    for change in &changes {
+        change.apply(&mut store);
+    }
+
+    save_stored_credentials(backend, &store).await?;
+
+    Ok(store)
+}
+
+// Result of a token refresh. Saving is reported apart from the refresh itself,
+// since the rotated tokens must be kept in memory even if they can't be saved.
+#[derive(Debug, Clone)]
+pub struct RefreshedCredentials {
+    pub store: CredentialStore,
+    // Change made by the refresh, if this process refreshed the tokens.
+    pub change: Option<CredentialsChange>,
+    pub save_error: Option<String>,
+}
+
+// Returns true when the credentials on disk were rotated by another process after
+// the in-memory copy was obtained.
+fn is_rotated_elsewhere(stored: &ClaudeCredentials, in_memory: &ClaudeCredentials) -> bool {
+    if stored.refresh_token == in_memory.refresh_token {
+        return false;
+    }
+
+    match (stored.obtained_at, in_memory.obtained_at) {
+        (Some(stored_at), Some(in_memory_at)) => stored_at > in_memory_at,
+        _ => true,
+    }
+}
+
+// Refreshes the tokens of a profile. Refresh tokens are single use, so the refresh
+// runs under a lock shared with every other instance of the applet. If another
+// process rotated the tokens while this one was waiting, its result is reused
+// instead of spending the already revoked refresh token.
+pub async fn refresh_profile_credentials(
+    backend: CredentialBackend,
+    store: CredentialStore,
+    profile_id: String,
+) -> Result<RefreshedCredentials, String> {
+    let in_memory = store
+        .profiles
+        .get(&profile_id)
+        .cloned()
+        .ok_or(format!("profile {profile_id} not found"))?;
+
+    let _lock = lock_credentials().await?;
+
+    // Start from the stored copy so profiles added by other processes are kept.
+    let mut latest_store = stored_credentials_or(backend, store).await?;
+
+    if let Some(stored) = latest_store.profiles.get(&profile_id)
+        && is_rotated_elsewhere(stored, &in_memory)
+    {
+        info!("tokens of profile {profile_id} were already refreshed by another process");
+        return Ok(RefreshedCredentials {
+            store: latest_store,
+            change: None,
+            save_error: None,
+        });
+    }
+
+    let token_response = claude::refresh_credentials(in_memory.refresh_token).await?;
+
+    let change = CredentialsChange::Refresh {
+        profile_id,
+        credentials: ClaudeCredentials::from(token_response),
+    };
+    change.apply(&mut latest_store);
+
+    let save_error = save_stored_credentials(backend, &latest_store).await.err();
+
+    if let Some(error) = &save_error {
+        error!("refreshed credentials could not be saved: {error}");
+    }
+
+    Ok(RefreshedCredentials {
+        store: latest_store,
+        change: Some(change),
+        save_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn account() -> Value {
+        json!({ "uuid": "account-uuid", "email_address": "user@example.com" })
+    }
+
+    fn organization() -> Value {
+        json!({ "uuid": "organization-uuid", "name": "Example" })
+    }
+
+    fn v2_credentials(account: Value) -> Value {
+        json!({
+            "version": 2,
+            "access_token": "access",
+            "refresh_token": "refresh",
+            "token_type": "Bearer",
+            "scopes": ["user:inference"],
+            "obtained_at": "2026-01-01T00:00:00Z",
+            "expires_at": "2026-01-01T08:00:00Z",
+            "account": account,
+            "organization": organization()
+        })
+    }
+
+    fn profile(account_uuid: &str, refresh_token: &str, obtained_at: &str) -> ClaudeCredentials {
+        ClaudeCredentials {
+            refresh_token: refresh_token.into(),
+            obtained_at: Some(obtained_at.parse().unwrap()),
+            account: Some(Account {
+                uuid: account_uuid.into(),
+                email_address: format!("{account_uuid}@example.com"),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn login_keeps_profiles_saved_by_other_processes() {
+        let mut store = CredentialStore::default();
+        store.insert(profile("other", "other-refresh", "2026-01-01T00:00:00Z"));
+
+        CredentialsChange::Login(profile("new", "new-refresh", "2026-01-02T00:00:00Z"))
+            .apply(&mut store);
+
+        assert_eq!(store.active_profile.as_deref(), Some("new"));
+        assert_eq!(store.profiles["other"].refresh_token, "other-refresh");
+        assert_eq!(store.profiles["new"].refresh_token, "new-refresh");
+    }
+
+    #[test]
+    fn refresh_never_overwrites_newer_tokens() {
+        let mut store = CredentialStore::default();
+        store.insert(profile("account", "newer-refresh", "2026-01-02T00:00:00Z"));
+
+        CredentialsChange::Refresh {
+            profile_id: "account".into(),
+            credentials: profile("account", "older-refresh", "2026-01-01T00:00:00Z"),
+        }
+        .apply(&mut store);
+
+        assert_eq!(store.profiles["account"].refresh_token, "newer-refresh");
+
+        CredentialsChange::Refresh {
+            profile_id: "account".into(),
+            credentials: profile("account", "latest-refresh", "2026-01-03T00:00:00Z"),
+        }
+        .apply(&mut store);
+
+        assert_eq!(store.profiles["account"].refresh_token, "latest-refresh");
+    }
+
+    #[test]
+    fn migrates_unversioned_v1_file() {
+        let file = migrate_credentials(json!({
+            "access_token": "access",
+            "refresh_token": "refresh"
+        }))
+        .unwrap();
+
+        assert_eq!(file.version, CREDENTIALS_SCHEMA_VERSION);
+        assert_eq!(
+            file.store.active_profile.as_deref(),
+            Some(LEGACY_PROFILE_ID)
+        );
+
+        let credentials = file.store.active().unwrap();
+        assert_eq!(credentials.access_token, "access");
+        assert_eq!(credentials.refresh_token, "refresh");
+        assert_eq!(credentials.token_type, "Bearer");
+        assert!(credentials.scopes.is_empty());
+        assert!(credentials.obtained_at.is_none());
+        assert!(credentials.expires_at.is_none());
+        assert!(credentials.account.is_none());
+        assert!(credentials.organization.is_none());
+    }
+
+    #[test]
+    fn migrates_v2_file_to_profile_of_its_account() {
+        let file = migrate_credentials(v2_credentials(account())).unwrap();
+
+        assert_eq!(file.version, CREDENTIALS_SCHEMA_VERSION);
+        assert_eq!(file.store.active_profile.as_deref(), Some("account-uuid"));
+        assert_eq!(file.store.profiles.len(), 1);
+
+        let credentials = file.store.active().unwrap();
+        assert_eq!(credentials.refresh_token, "refresh");
+        assert_eq!(credentials.scopes, ["user:inference"]);
+        assert!(credentials.expires_at.is_some());
+        assert_eq!(
+            credentials.account.as_ref().unwrap().email_address,
+            "user@example.com"
+        );
+        assert_eq!(credentials.organization.as_ref().unwrap().name, "Example");
+    }
+
+    #[test]
+    fn migrates_v2_file_without_account_to_legacy_profile() {
+        let file = migrate_credentials(v2_credentials(Value::Null)).unwrap();
+
+        assert_eq!(
+            file.store.active_profile.as_deref(),
+            Some(LEGACY_PROFILE_ID)
+        );
+
+        let credentials = file.store.active().unwrap();
+        assert_eq!(credentials.refresh_token, "refresh");
+        assert!(credentials.account.is_none());
+    }
+
+    #[test]
+    fn keeps_current_file_unchanged() {
+        let raw = json!({
+            "version": CREDENTIALS_SCHEMA_VERSION,
+            "active_profile": "account-uuid",
+            "profiles": {
+                "account-uuid": {
+                    "access_token": "access",
+                    "refresh_token": "refresh",
+                    "token_type": "Bearer",
+                    "scopes": ["user:inference"],
+                    "obtained_at": "2026-01-01T00:00:00Z",
+                    "expires_at": "2026-01-01T08:00:00Z",
+                    "account": account(),
+                    "organization": organization()
+                }
+            }
+        });
+
+        let file = migrate_credentials(raw.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(&file).unwrap(), raw);
+    }
+
+    #[test]
+    fn rejects_file_of_newer_version() {
+        let error = migrate_credentials(json!({
+            "version": CREDENTIALS_SCHEMA_VERSION + 1,
+            "active_profile": null,
+            "profiles": {}
+        }))
+        .unwrap_err();
+
+        assert!(error.contains("update the applet"));
+    }
+
+    #[test]
+    fn rejects_non_object_file() {
+        assert!(migrate_credentials(json!(["access", "refresh"])).is_err());
+        assert!(migrate_credentials(json!("refresh")).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_version() {
+        for version in [
+            json!("3"),
+            json!(-1),
+            json!(1.5),
+            json!(u64::MAX),
+            Value::Null,
+        ] {
+            let raw = json!({
+                "version": version,
+                "access_token": "access",
+                "refresh_token": "refresh"
+            });
+
+            assert!(migrate_credentials(raw).is_err());
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::fl;
+
+/// Operation that failed, which decides the message shown and the action suggested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Login,
+    TokenRefresh,
+    Usage,
+    Credentials,
+    CredentialsSave,
+    CredentialsMove,
+}
+
+/// What the user can do to recover from an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestedAction {
+    Retry,
+    Login,
+}
+
+/// Error shown to the user in the popup.
+#[derive(Debug, Clone)]
+pub struct AppError {
+    pub kind: ErrorKind,
+    /// Technical description of the failure, included in the copied details.
+    pub details: String,
+    /// Identifier of the failed API request, if the API returned one.
+    pub request_id: Option<String>,
+}
+
+impl AppError {
+    pub fn new(kind: ErrorKind, details: impl Into<String>) -> Self {
+        Self {
+            kind,
+            details: details.into(),
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Human readable message in the language of the user.
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::Login => fl!("error-login"),
+            ErrorKind::TokenRefresh => fl!("error-token-refresh"),
+            ErrorKind::Usage => fl!("error-usage"),
+            ErrorKind::Credentials => fl!("error-credentials"),
+            ErrorKind::CredentialsSave => fl!("error-credentials-save"),
+            ErrorKind::CredentialsMove => fl!("error-credentials-move"),
+        }
+    }
+
+    pub fn suggested_action(&self) -> SuggestedAction {
+        match self.kind {
+            ErrorKind::Usage | ErrorKind::CredentialsSave | ErrorKind::CredentialsMove => {
+                SuggestedAction::Retry
+            }
+            ErrorKind::Login | ErrorKind::TokenRefresh | ErrorKind::Credentials => {
+                SuggestedAction::Login
+            }
+        }
+    }
+
+    /// Whether the user can hide the error. Unsaved credentials stay visible until
+    /// saving them succeeds.
+    pub fn is_dismissible(&self) -> bool {
+        self.kind != ErrorKind::CredentialsSave
+    }
+
+    /// Everything known about the error, for bug reports and support requests.
+    pub fn report(&self) -> String {
+        let mut report = format!("{}\n{}", self.message(), self.details);
+
+        if let Some(request_id) = &self.request_id {
+            report.push_str(&format!("\nrequest_id: {request_id}"));
+        }
+
+        report
+    }
+}
@@ -0,0 +1,76 @@
+use crate::utils::write_atomically;
+use crate::{app::Message, credentials};
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures_util::SinkExt;
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+
+// Every panel or dock hosting the applet runs its own process. Only the instance
+// holding the monitor lock polls the API and refreshes tokens, the others read the
+// usage cache it publishes. The operating system releases the lock when the process
+// exits, even if it crashes, and the next waiting instance takes over.
+pub async fn leader_election(channel: &mut Sender<Message>) {
+    let lock_file = match open_lock_file() {
+        Ok(lock_file) => lock_file,
+        Err(error) => {
+            // Polling from every instance is better than not polling at all.
+            log::error!("leader election not possible, polling from this instance: {error}");
+            let _ = channel.send(Message::BecameLeader).await;
+            return;
+        }
+    };
+
+    // The lock is held, not used, for as long as the application runs.
+    let _lock_file = match lock_file.try_lock() {
+        Ok(()) => lock_file,
+        Err(_) => {
+            log::info!("another applet instance is polling, waiting for it to exit");
+
+            match tokio::task::spawn_blocking(move || lock_file.lock().map(|()| lock_file)).await {
+                Ok(Ok(lock_file)) => lock_file,
+                Ok(Err(error)) => {
+                    log::error!(
+                        "failed to lock monitor lock file, polling from this instance: {error}"
+                    );
+                    let _ = channel.send(Message::BecameLeader).await;
+                    return;
+                }
+                Err(error) => {
+                    log::error!("failed to wait for the monitor lock: {error}");
+                    return;
+                }
+            }
+        }
+    };
+
+    log::info!("this applet instance polls the usage api now");
+    let _ = channel.send(Message::BecameLeader).await;
+
+    std::future::pending::<()>().await;
+}
+
+fn open_lock_file() -> Result<File, String> {
+    let config_dir = credentials::ensure_config_dir()?;
+
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(config_dir.join("monitor.lock"))
+        .map_err(|e| format!("failed to open monitor lock file: {e}"))
+}
+
+// Returns the path of the file through which the other instances ask the polling
+// one for a fetch, $HOME/.config/claude-tray/fetch.request.
+pub fn fetch_request_path() -> Result<PathBuf, String> {
+    Ok(credentials::config_dir()?.join("fetch.request"))
+}
+
+// Asks the polling instance to fetch usage data right away. It watches the request
+// file and fetches every time it is written.
+pub fn request_fetch() -> Result<(), String> {
+    credentials::ensure_config_dir()?;
+
+    write_atomically(&fetch_request_path()?, &chrono::Utc::now().to_rfc3339())
+        .map_err(|e| format!("failed to write fetch request: {e}"))
+}
@@ -0,0 +1,150 @@
+use crate::app::Message;
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures_util::{SinkExt, StreamExt};
+
+// Proxy for the session manager of systemd-logind.
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    // Emitted with `true` right before the system suspends and with `false` after it resumes.
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+// Notifies the application when the system is about to suspend and when it resumes.
+pub async fn sleep_monitoring(channel: &mut Sender<Message>) {
+    let connection = match zbus::Connection::system().await {
+        Ok(connection) => connection,
+        Err(error) => {
+            log::warn!("suspend monitoring not started, system bus unavailable: {error}");
+            return;
+        }
+    };
+
+    if let Err(error) = watch_prepare_for_sleep(&connection, channel).await {
+        log::warn!("suspend monitoring stopped: {error}");
+    }
+}
+
+// Forwards `PrepareForSleep` signals received through `connection` to the application.
+// The connection is a parameter so the signal can come from a private test bus.
+async fn watch_prepare_for_sleep(
+    connection: &zbus::Connection,
+    channel: &mut Sender<Message>,
+) -> zbus::Result<()> {
+    let manager = ManagerProxy::new(connection).await?;
+    let mut signals = manager.receive_prepare_for_sleep().await?;
+
+    log::info!("suspend monitoring started");
+
+    while let Some(signal) = signals.next().await {
+        let is_suspending = signal.args()?.start;
+
+        if is_suspending {
+            log::info!("system is about to suspend");
+        } else {
+            log::info!("system resumed");
+        }
+
+        if channel
+            .send(Message::PrepareForSleep(is_suspending))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+    use zbus::object_server::SignalEmitter;
+
+    // Stand-in for logind on a private bus.
+    struct FakeManager;
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl FakeManager {
+        #[zbus(signal)]
+        async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+    }
+
+    // Runs a private dbus-daemon and returns it with its address.
+    fn start_test_bus() -> Option<(std::process::Child, String)> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+
+        Some((daemon, address.trim().to_string()))
+    }
+
+    #[tokio::test]
+    async fn forwards_prepare_for_sleep_signals() {
+        let (mut daemon, address) =
+            start_test_bus().expect("dbus-daemon is needed to run this test");
+
+        let logind = zbus::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name("org.freedesktop.login1")
+            .unwrap()
+            .serve_at("/org/freedesktop/login1", FakeManager)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let applet = zbus::connection::Builder::address(address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let (mut sender, mut receiver) = cosmic::iced::futures::channel::mpsc::channel(10);
+        tokio::spawn(async move {
+            let _ = watch_prepare_for_sleep(&applet, &mut sender).await;
+        });
+
+        let emitter = logind
+            .object_server()
+            .interface::<_, FakeManager>("/org/freedesktop/login1")
+            .await
+            .unwrap()
+            .signal_emitter()
+            .clone();
+
+        // The watcher subscribes asynchronously, so keep emitting until it listens.
+        let received = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                FakeManager::prepare_for_sleep(&emitter, true)
+                    .await
+                    .unwrap();
+
+                if let Ok(message) =
+                    tokio::time::timeout(Duration::from_millis(100), receiver.next()).await
+                {
+                    break message;
+                }
+            }
+        })
+        .await;
+
+        let _ = daemon.kill();
+
+        assert!(matches!(received, Ok(Some(Message::PrepareForSleep(true)))));
+    }
+}
@@ -1,9 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod app;
+mod budget;
 mod claude;
 mod claude_monitor;
+mod config;
+mod credentials;
+mod error;
 mod i18n;
+mod leader;
+mod logind;
+mod notifications;
+mod poll_scheduler;
+mod power;
+mod shared_files_monitor;
+mod usage_cache;
+mod usage_history;
 mod utils;
 
 fn main() -> cosmic::iced::Result {
@@ -16,6 +28,7 @@ fn main() -> cosmic::iced::Result {
 
     // Enable localizations to be applied.
     i18n::init(&requested_languages);
+    budget::init(&requested_languages);
 
     // Starts the applet's event loop with `()` as the application's flags.
     cosmic::applet::run::<app::AppModel>(())
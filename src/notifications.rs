@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+/// Name shown as the sender of the notifications.
+const APP_NAME: &str = "Claude Applet";
+
+// Proxy for the desktop notification server.
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, zbus::zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+// Shows a desktop notification through the notification server of the session.
+pub async fn send_notification(icon: &str, summary: &str, body: &str) -> Result<(), String> {
+    let connection = zbus::Connection::session()
+        .await
+        .map_err(|e| format!("session bus unavailable: {e}"))?;
+
+    let notifications = NotificationsProxy::new(&connection)
+        .await
+        .map_err(|e| format!("notification server unavailable: {e}"))?;
+
+    notifications
+        .notify(APP_NAME, 0, icon, summary, body, &[], HashMap::new(), -1)
+        .await
+        .map_err(|e| format!("failed to send notification: {e}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use crate::claude::ClaudeUsageResponse;
+use crate::config::{Config, MIN_POLL_INTERVAL};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Number of polls without changes after which polling starts to back off.
+const UNCHANGED_POLLS_BEFORE_BACKOFF: u32 = 3;
+/// Largest multiple of the poll interval used while values don't change.
+const MAX_BACKOFF_FACTOR: u32 = 4;
+/// Time waited after a `resets_at` moment so the API already reports the new window.
+const RESET_MARGIN: Duration = Duration::from_secs(5);
+/// Delay before retrying after the first failed request. It doubles on every failure.
+const FAILURE_RETRY_DELAY: Duration = Duration::from_secs(15);
+/// Largest multiple of the poll interval waited after API errors.
+const MAX_FAILURE_BACKOFF_FACTOR: u32 = 4;
+
+/// Utilization, in percent, from which each usage period is close to its limit. These
+/// are the warning thresholds of the configuration, so polling speeds up at the same
+/// point the applet starts warning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarningThresholds {
+    default: f32,
+    periods: BTreeMap<String, f32>,
+}
+
+impl WarningThresholds {
+    fn get(&self, period: &str) -> f32 {
+        self.periods.get(period).copied().unwrap_or(self.default)
+    }
+}
+
+impl From<&Config> for WarningThresholds {
+    fn from(config: &Config) -> Self {
+        Self {
+            default: config.default_thresholds.warning as f32,
+            periods: config
+                .period_thresholds
+                .iter()
+                .map(|(period, thresholds)| (period.clone(), thresholds.warning as f32))
+                .collect(),
+        }
+    }
+}
+
+// Decides how long the monitor waits between two usage requests. Polling speeds up
+// while utilization rises or gets close to a limit, slows down while nothing changes
+// and always wakes up right after a usage period resets. Failed requests are retried
+// with an exponential backoff.
+pub struct PollScheduler {
+    poll_interval: Duration,
+    warning_thresholds: WarningThresholds,
+    previous_utilization: Option<Vec<f32>>,
+    unchanged_polls: u32,
+    consecutive_failures: u32,
+}
+
+impl PollScheduler {
+    pub fn new(poll_interval: Duration, warning_thresholds: WarningThresholds) -> Self {
+        Self {
+            poll_interval,
+            warning_thresholds,
+            previous_utilization: None,
+            unchanged_polls: 0,
+            consecutive_failures: 0,
+        }
+    }
+
+    // Changes the regular interval the adaptive delays are based on.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    // Changes the utilization from which polling speeds up.
+    pub fn set_warning_thresholds(&mut self, warning_thresholds: WarningThresholds) {
+        self.warning_thresholds = warning_thresholds;
+    }
+
+    // Number of requests that failed in a row.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    // Records a failed fetch and returns the delay before retrying. While offline the
+    // delay never exceeds the poll interval. The application also asks for a fetch as
+    // soon as the network is back, so recovery doesn't wait for the retry.
+    pub fn next_delay_after_failure(&mut self, is_connection_error: bool) -> Duration {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+
+        let backoff =
+            FAILURE_RETRY_DELAY.saturating_mul(2_u32.saturating_pow(self.consecutive_failures - 1));
+
+        let max_delay = if is_connection_error {
+            self.poll_interval
+        } else {
+            self.poll_interval
+                .saturating_mul(MAX_FAILURE_BACKOFF_FACTOR)
+        };
+
+        backoff.min(max_delay)
+    }
+
+    // Records a successful fetch and returns the delay before the next one.
+    pub fn next_delay(&mut self, usage: &ClaudeUsageResponse, now: DateTime<Utc>) -> Duration {
+        self.consecutive_failures = 0;
+
+        let periods = usage.periods();
+        let utilization: Vec<f32> = periods
+            .iter()
+            .map(|(_, period)| period.utilization)
+            .collect();
+
+        let is_rising = self.previous_utilization.as_ref().is_some_and(|previous| {
+            previous.len() == utilization.len()
+                && previous
+                    .iter()
+                    .zip(&utilization)
+                    .any(|(previous, current)| current > previous)
+        });
+
+        if self.previous_utilization.as_ref() == Some(&utilization) {
+            self.unchanged_polls = self.unchanged_polls.saturating_add(1);
+        } else {
+            self.unchanged_polls = 0;
+        }
+
+        self.previous_utilization = Some(utilization);
+
+        let is_near_limit = periods
+            .iter()
+            .any(|(name, period)| period.utilization >= self.warning_thresholds.get(name));
+
+        let delay = match (is_rising, is_near_limit) {
+            (true, true) => self.poll_interval / 4,
+            (true, false) | (false, true) => self.poll_interval / 2,
+            (false, false) if self.unchanged_polls >= UNCHANGED_POLLS_BEFORE_BACKOFF => {
+                let factor = 2_u32
+                    .saturating_pow(self.unchanged_polls + 1 - UNCHANGED_POLLS_BEFORE_BACKOFF)
+                    .min(MAX_BACKOFF_FACTOR);
+
+                self.poll_interval.saturating_mul(factor)
+            }
+            (false, false) => self.poll_interval,
+        }
+        .max(Duration::from_secs(MIN_POLL_INTERVAL));
+
+        // Wake up right after the earliest upcoming reset, even if it comes before the
+        // delay, so the new window is shown as soon as it starts.
+        let until_reset = periods
+            .iter()
+            .filter_map(|(_, period)| period.resets_at)
+            .filter(|resets_at| *resets_at > now)
+            .min()
+            .and_then(|resets_at| (resets_at - now).to_std().ok())
+            .map(|until_reset| until_reset + RESET_MARGIN);
+
+        until_reset.map_or(delay, |until_reset| delay.min(until_reset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UsageThresholds;
+
+    const POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+    // Scheduler that speeds up from 80% on.
+    fn scheduler() -> PollScheduler {
+        let config = Config {
+            default_thresholds: UsageThresholds {
+                warning: 80,
+                critical: 95,
+            },
+            ..Config::default()
+        };
+
+        PollScheduler::new(POLL_INTERVAL, WarningThresholds::from(&config))
+    }
+
+    fn usage(
+        five_hour: (f32, Option<DateTime<Utc>>),
+        seven_day: (f32, Option<DateTime<Utc>>),
+    ) -> ClaudeUsageResponse {
+        serde_json::from_value(serde_json::json!({
+            "five_hour": { "utilization": five_hour.0, "resets_at": five_hour.1 },
+            "seven_day": { "utilization": seven_day.0, "resets_at": seven_day.1 },
+            "seven_day_oauth_apps": null,
+            "seven_day_opus": null,
+            "seven_day_sonnet": null,
+            "iguana_necktie": null,
+            "seven_day_iguana_necktie": null,
+            "extra_usage": {
+                "is_enabled": false,
+                "monthly_limit": null,
+                "used_credits": null,
+                "utilization": null
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn speeds_up_while_utilization_rises() {
+        let mut scheduler = scheduler();
+        let now = Utc::now();
+
+        assert_eq!(
+            scheduler.next_delay(&usage((10.0, None), (20.0, None)), now),
+            POLL_INTERVAL
+        );
+        assert_eq!(
+            scheduler.next_delay(&usage((15.0, None), (20.0, None)), now),
+            POLL_INTERVAL / 2
+        );
+    }
+
+    #[test]
+    fn speeds_up_from_the_warning_threshold() {
+        let mut scheduler = scheduler();
+        let now = Utc::now();
+
+        assert_eq!(
+            scheduler.next_delay(&usage((80.0, None), (20.0, None)), now),
+            POLL_INTERVAL / 2
+        );
+        assert_eq!(
+            scheduler.next_delay(&usage((85.0, None), (20.0, None)), now),
+            POLL_INTERVAL / 4
+        );
+    }
+
+    #[test]
+    fn backs_off_while_nothing_changes() {
+        let mut scheduler = scheduler();
+        let now = Utc::now();
+        let usage = usage((10.0, None), (20.0, None));
+
+        let delays: Vec<_> = (0..=UNCHANGED_POLLS_BEFORE_BACKOFF + 2)
+            .map(|_| scheduler.next_delay(&usage, now))
+            .collect();
+
+        assert_eq!(
+            delays,
+            [
+                POLL_INTERVAL,
+                POLL_INTERVAL,
+                POLL_INTERVAL,
+                POLL_INTERVAL * 2,
+                POLL_INTERVAL * MAX_BACKOFF_FACTOR,
+                POLL_INTERVAL * MAX_BACKOFF_FACTOR,
+            ]
+        );
+    }
+
+    #[test]
+    fn backoff_saturates_with_huge_poll_intervals() {
+        let mut scheduler = scheduler();
+        scheduler.set_poll_interval(Duration::MAX);
+        let now = Utc::now();
+        let usage = usage((10.0, None), (20.0, None));
+
+        for _ in 0..=UNCHANGED_POLLS_BEFORE_BACKOFF + 1 {
+            assert_eq!(scheduler.next_delay(&usage, now), Duration::MAX);
+        }
+    }
+
+    #[test]
+    fn failures_back_off_up_to_a_multiple_of_the_poll_interval() {
+        let mut scheduler = scheduler();
+
+        let delays: Vec<_> = (0..8)
+            .map(|_| scheduler.next_delay_after_failure(false))
+            .collect();
+
+        assert_eq!(
+            delays,
+            [15, 30, 60, 120, 240, 480, 960, 1200].map(Duration::from_secs)
+        );
+        assert_eq!(scheduler.consecutive_failures(), 8);
+
+        scheduler.next_delay(&usage((10.0, None), (20.0, None)), Utc::now());
+        assert_eq!(scheduler.consecutive_failures(), 0);
+    }
+
+    #[test]
+    fn retries_within_the_poll_interval_while_offline() {
+        let mut scheduler = scheduler();
+
+        let delays: Vec<_> = (0..6)
+            .map(|_| scheduler.next_delay_after_failure(true))
+            .collect();
+
+        assert_eq!(delays, [15, 30, 60, 120, 240, 300].map(Duration::from_secs));
+    }
+
+    #[test]
+    fn wakes_up_after_the_earliest_upcoming_reset() {
+        let mut scheduler = scheduler();
+        let now = Utc::now();
+
+        let delay = scheduler.next_delay(
+            &usage(
+                (10.0, Some(now + chrono::Duration::seconds(120))),
+                (20.0, Some(now + chrono::Duration::seconds(60))),
+            ),
+            now,
+        );
+
+        assert_eq!(delay, Duration::from_secs(60) + RESET_MARGIN);
+    }
+
+    #[test]
+    fn ignores_past_resets() {
+        let mut scheduler = scheduler();
+        let now = Utc::now();
+
+        let delay = scheduler.next_delay(
+            &usage(
+                (10.0, Some(now - chrono::Duration::seconds(60))),
+                (20.0, None),
+            ),
+            now,
+        );
+
+        assert_eq!(delay, POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::app::Message;
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures_util::{SinkExt, StreamExt};
+
+/// Values of the `NMMetered` enum of NetworkManager that mean the connection is metered.
+const NM_METERED_YES: u32 = 1;
+const NM_METERED_GUESS_YES: u32 = 3;
+/// Value of the `NMConnectivityState` enum of NetworkManager for full internet access.
+const NM_CONNECTIVITY_FULL: u32 = 4;
+
+// Proxy for the UPower daemon.
+#[zbus::proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower"
+)]
+trait UPower {
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+}
+
+// Proxy for the NetworkManager daemon.
+#[zbus::proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    #[zbus(property)]
+    fn metered(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn connectivity(&self) -> zbus::Result<u32>;
+}
+
+/// Power source and network cost, which decide how often usage is polled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerState {
+    pub is_on_battery: bool,
+    pub is_metered: bool,
+}
+
+fn is_metered(metered: u32) -> bool {
+    matches!(metered, NM_METERED_YES | NM_METERED_GUESS_YES)
+}
+
+// Notifies the application every time the power source or the network cost changes,
+// and when internet access comes back. Missing services are treated as running on AC
+// power and an unmetered network.
+pub async fn power_state_monitoring(channel: &mut Sender<Message>) {
+    let connection = match zbus::Connection::system().await {
+        Ok(connection) => connection,
+        Err(error) => {
+            log::warn!("power state monitoring not started, system bus unavailable: {error}");
+            return;
+        }
+    };
+
+    if let Err(error) = watch_power_state(&connection, channel).await {
+        log::warn!("power state monitoring stopped: {error}");
+    }
+}
+
+// Forwards the power state read through `connection` to the application.
+async fn watch_power_state(
+    connection: &zbus::Connection,
+    channel: &mut Sender<Message>,
+) -> zbus::Result<()> {
+    let upower = UPowerProxy::new(connection).await?;
+    let network_manager = NetworkManagerProxy::new(connection).await?;
+
+    let mut state = PowerState {
+        is_on_battery: upower.on_battery().await.unwrap_or(false),
+        is_metered: network_manager.metered().await.is_ok_and(is_metered),
+    };
+
+    let mut is_connected = network_manager
+        .connectivity()
+        .await
+        .is_ok_and(|connectivity| connectivity == NM_CONNECTIVITY_FULL);
+
+    log::info!("power state monitoring started: {state:?}");
+
+    if channel
+        .send(Message::PowerStateChanged(state))
+        .await
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    let mut on_battery_changes = upower.receive_on_battery_changed().await;
+    let mut metered_changes = network_manager.receive_metered_changed().await;
+    let mut connectivity_changes = network_manager.receive_connectivity_changed().await;
+
+    loop {
+        let previous_state = state;
+
+        tokio::select! {
+            Some(change) = on_battery_changes.next() => {
+                state.is_on_battery = change.get().await?;
+            }
+            Some(change) = metered_changes.next() => {
+                state.is_metered = is_metered(change.get().await?);
+            }
+            Some(change) = connectivity_changes.next() => {
+                let was_connected = is_connected;
+                is_connected = change.get().await? == NM_CONNECTIVITY_FULL;
+
+                if is_connected && !was_connected {
+                    log::info!("internet access restored");
+
+                    if channel.send(Message::ConnectivityRestored).await.is_err() {
+                        break;
+                    }
+                }
+
+                continue;
+            }
+            else => break,
+        }
+
+        if state == previous_state {
+            continue;
+        }
+
+        log::info!("power state changed: {state:?}");
+
+        if channel
+            .send(Message::PowerStateChanged(state))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
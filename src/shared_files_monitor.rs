@@ -0,0 +1,134 @@
+use crate::{app::Message, credentials, leader, usage_cache};
+use cosmic::iced::futures::channel::mpsc::Sender;
+use futures_util::SinkExt;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Time given to the writer to finish before the file is read again. Editors and
+/// other tools usually rewrite a file in several steps.
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(250);
+
+/// Files shared between applet instances and other processes, and the message sent
+/// when each of them changes.
+struct SharedFile {
+    path: PathBuf,
+    message: Message,
+}
+
+// Watches the files shared with other processes through inotify: the credentials,
+// or the file that tells they changed in the Secret Service, the usage cache and the
+// fetch requests. Notifies the application every time one of them is rewritten.
+pub async fn shared_files_monitoring(channel: &mut Sender<Message>) {
+    let shared_files = match shared_files() {
+        Ok(shared_files) => shared_files,
+        Err(error) => {
+            log::error!("shared files monitoring not started: {error}");
+            return;
+        }
+    };
+
+    // The directory is watched instead of the files because tools usually replace a
+    // file with a rename, which would drop a watch on the file itself.
+    let config_dir = match credentials::ensure_config_dir() {
+        Ok(config_dir) => config_dir,
+        Err(error) => {
+            log::error!("shared files monitoring not started: {error}");
+            return;
+        }
+    };
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    let mut watcher = match notify::recommended_watcher(move |event: notify::Result<_>| {
+        let _ = sender.send(event);
+    }) {
+        Ok(watcher) => watcher,
+        Err(error) => {
+            log::error!("failed to create shared files watcher: {error}");
+            return;
+        }
+    };
+
+    if let Err(error) = watcher.watch(&config_dir, RecursiveMode::NonRecursive) {
+        log::error!("failed to watch {}: {error}", config_dir.display());
+        return;
+    }
+
+    log::info!(
+        "shared files monitoring started on {}",
+        config_dir.display()
+    );
+
+    while let Some(event) = receiver.recv().await {
+        let event = match event {
+            Ok(event) => event,
+            Err(error) => {
+                log::warn!("shared files watcher error: {error}");
+                continue;
+            }
+        };
+
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            continue;
+        }
+
+        let mut is_written = written_files(&shared_files, &event.paths);
+
+        if !is_written.contains(&true) {
+            continue;
+        }
+
+        // Collapse the burst of events produced by a single write. Events for the
+        // other files arriving meanwhile are collected too.
+        tokio::time::sleep(DEBOUNCE_DELAY).await;
+        while let Ok(event) = receiver.try_recv() {
+            if let Ok(event) = event {
+                for (is_written, is_written_now) in is_written
+                    .iter_mut()
+                    .zip(written_files(&shared_files, &event.paths))
+                {
+                    *is_written |= is_written_now;
+                }
+            }
+        }
+
+        for (shared_file, _) in shared_files
+            .iter()
+            .zip(is_written)
+            .filter(|(_, is_written)| *is_written)
+        {
+            log::debug!("{} changed on disk", shared_file.path.display());
+            let _ = channel.send(shared_file.message.clone()).await;
+        }
+    }
+}
+
+fn shared_files() -> Result<Vec<SharedFile>, String> {
+    Ok(vec![
+        SharedFile {
+            path: credentials::credentials_path()?,
+            message: Message::CredentialsFileChanged,
+        },
+        SharedFile {
+            path: credentials::secret_changed_path()?,
+            message: Message::CredentialsFileChanged,
+        },
+        SharedFile {
+            path: usage_cache::usage_cache_path()?,
+            message: Message::UsageCacheChanged,
+        },
+        SharedFile {
+            path: leader::fetch_request_path()?,
+            message: Message::FetchRequested,
+        },
+    ])
+}
+
+// Tells for every shared file whether it is one of the paths of an event.
+fn written_files(shared_files: &[SharedFile], paths: &[PathBuf]) -> Vec<bool> {
+    shared_files
+        .iter()
+        .map(|shared_file| paths.contains(&shared_file.path))
+        .collect()
+}
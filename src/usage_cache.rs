@@ -0,0 +1,46 @@
+use crate::utils::write_atomically;
+use crate::{claude::ClaudeUsageResponse, credentials};
+use chrono::{DateTime, Utc};
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// Last usage data fetched by the polling instance, read by the other applet
+// instances instead of polling the API themselves.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UsageCache {
+    pub profile_id: String,
+    pub fetched_at: DateTime<Utc>,
+    pub usage: ClaudeUsageResponse,
+}
+
+// Returns the path of the usage cache, $HOME/.config/claude-tray/usage.json.
+pub fn usage_cache_path() -> Result<PathBuf, String> {
+    Ok(credentials::config_dir()?.join("usage.json"))
+}
+
+pub fn get_usage_cache() -> Result<UsageCache, String> {
+    let cache_file = usage_cache_path()?;
+
+    trace!("reading usage cache from {}", cache_file.display());
+
+    let cache_json =
+        fs::read_to_string(&cache_file).map_err(|e| format!("failed to read usage cache: {e}"))?;
+
+    serde_json::from_str(&cache_json).map_err(|e| format!("failed to parse usage cache: {e}"))
+}
+
+pub fn save_usage_cache(cache: &UsageCache) -> Result<(), String> {
+    let cache_file = usage_cache_path()?;
+
+    trace!("saving usage cache to {}", cache_file.display());
+
+    credentials::ensure_config_dir()?;
+
+    let cache_json = serde_json::to_string_pretty(cache)
+        .map_err(|e| format!("failed to serialize usage cache: {e}"))?;
+
+    write_atomically(&cache_file, &cache_json)
+        .map_err(|e| format!("failed to write usage cache: {e}"))
+}
@@ -0,0 +1,83 @@
+use crate::claude::ClaudeUsageResponse;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+/// How far back the usage history goes.
+pub const HISTORY_SPAN: Duration = Duration::hours(24);
+
+/// Utilization of every usage period reported at one moment.
+#[derive(Debug, Clone)]
+struct UsageSample {
+    at: DateTime<Utc>,
+    utilization: Vec<(&'static str, f32)>,
+}
+
+/// Usage of the active profile over the last `HISTORY_SPAN`, oldest first. It only lives
+/// in memory, so it starts empty every time the applet starts.
+#[derive(Debug, Clone, Default)]
+pub struct UsageHistory {
+    samples: VecDeque<UsageSample>,
+}
+
+impl UsageHistory {
+    // Adds the usage fetched at the given moment and forgets samples older than the
+    // history span. Usage that isn't newer than the last sample is ignored.
+    pub fn record(&mut self, usage: &ClaudeUsageResponse, at: DateTime<Utc>) {
+        if self.samples.back().is_some_and(|sample| sample.at >= at) {
+            return;
+        }
+
+        self.samples.push_back(UsageSample {
+            at,
+            utilization: usage
+                .periods()
+                .into_iter()
+                .map(|(name, period)| (name, period.utilization))
+                .collect(),
+        });
+
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| at.signed_duration_since(sample.at) > HISTORY_SPAN)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    // Splits the history span until `now` into `slots` equal slots and returns the
+    // highest utilization of the period in each one, oldest first. Slots without
+    // samples are `None`.
+    pub fn peaks(&self, period: &str, now: DateTime<Utc>, slots: usize) -> Vec<Option<f32>> {
+        let mut peaks = vec![None; slots];
+        let slot_seconds = HISTORY_SPAN.num_seconds() / i64::try_from(slots.max(1)).unwrap_or(1);
+        let start = now - HISTORY_SPAN;
+
+        for sample in &self.samples {
+            let Some(utilization) = sample
+                .utilization
+                .iter()
+                .find(|(name, _)| *name == period)
+                .map(|(_, utilization)| *utilization)
+            else {
+                continue;
+            };
+
+            // Samples before the start of the span don't fit in any slot.
+            let offset = sample.at.signed_duration_since(start).num_seconds();
+            let Ok(index) = usize::try_from(offset.div_euclid(slot_seconds)) else {
+                continue;
+            };
+
+            if let Some(peak) = peaks.get_mut(index) {
+                *peak = Some(peak.map_or(utilization, |peak: f32| peak.max(utilization)));
+            }
+        }
+
+        peaks
+    }
+}
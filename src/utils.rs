@@ -1,3 +1,6 @@
+use std::fs;
+use std::path::Path;
+
 // Extracts a parameters values from an URL
 pub fn extract_param_from_url(request: &str, param_name: &str) -> Result<String, String> {
     let search = format!("{param_name}=");
@@ -10,3 +13,13 @@ pub fn extract_param_from_url(request: &str, param_name: &str) -> Result<String,
 
     Ok(param_part[..param_end].to_string())
 }
+
+// Writes a file through a temporary file that is then renamed over it, so other
+// processes never read it partially written.
+pub fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut temporary_file = path.as_os_str().to_owned();
+    temporary_file.push(".tmp");
+
+    fs::write(&temporary_file, contents)?;
+    fs::rename(&temporary_file, path)
+}